@@ -1,16 +1,27 @@
 use std::path::Path;
+use std::thread;
 use std::vec::Vec;
 use std::{fs::File, time::Duration};
 use std::{
     fs::OpenOptions,
-    io::{BufWriter, Read, Write},
+    io::{self, BufRead, BufWriter, Read, Write},
 };
 
+use crate::audio_sink::{to_i16, RingBufferReader};
 use crate::cpu::*;
+use crate::input::KeyEvents;
+use crate::recorder::GifCapture;
 use crate::vram::*;
 
+const SCREENSHOT_PREFIX: &str = "screenshot_";
+const GIF_PREFIX: &str = "capture_";
+const HOLD_PREFIX: char = '+';
+const RELEASE_PREFIX: char = '-';
+const STREAM_CHUNK: usize = 1024; //Samples moved from the ring buffer per write
+
 pub struct WindowsInterface {
     buff: BufWriter<File>,
+    screenshot_scale: usize,
 }
 
 impl WindowsInterface {
@@ -29,7 +40,94 @@ impl WindowsInterface {
 
         let buffer = BufWriter::new(log_file);
 
-        Self { buff: buffer }
+        Self {
+            buff: buffer,
+            screenshot_scale: 1,
+        }
+    }
+
+    pub fn set_screenshot_scale(&mut self, scale: usize) {
+        self.screenshot_scale = scale;
+    }
+
+    //Reads keys typed in the console and sends them to key_events.  Each line holds key names separated by spaces,
+    //e.g. "enter" or "+right x -right".  A plain name taps the key, +name holds it down until -name releases it.
+    pub fn read_console_keys(key_events: KeyEvents) {
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+
+                for word in line.split_whitespace() {
+                    if let Some(key) = word.strip_prefix(HOLD_PREFIX) {
+                        key_events.key_down(key);
+                    } else if let Some(key) = word.strip_prefix(RELEASE_PREFIX) {
+                        key_events.key_up(key);
+                    } else {
+                        key_events.key_down(word);
+                        key_events.key_up(word);
+                    }
+                }
+            }
+        });
+    }
+
+    //Plays the part of a real-time audio callback: drains the ring buffer into `output` as 16-bit stereo PCM on its
+    //own thread, e.g. into a named pipe read by a player.  Stops when the output can't be written any more.
+    pub fn stream_audio(mut reader: RingBufferReader, output: File) {
+        thread::spawn(move || {
+            let mut output = BufWriter::new(output);
+            let mut samples = [0.0; STREAM_CHUNK];
+            loop {
+                let count = reader.read(&mut samples);
+                if count == 0 {
+                    if output.flush().is_err() {
+                        break;
+                    }
+                    Self::sleep();
+                    continue;
+                }
+
+                let bytes: Vec<u8> = samples[..count]
+                    .iter()
+                    .flat_map(|sample| to_i16(*sample).to_le_bytes())
+                    .collect();
+                if output.write_all(&bytes).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    //Saves the last frame as screenshot_<n>.png in the working directory, using the first free number.
+    //Returns the path written.  Bound to the screenshot hotkey, f12 by default.
+    pub fn save_screenshot(&self, cpu: &Cpu) -> std::io::Result<String> {
+        let path = Self::next_free_path(SCREENSHOT_PREFIX, "png");
+        cpu.save_screenshot(&path, self.screenshot_scale)?;
+
+        Ok(path)
+    }
+
+    //Saves the recent gameplay kept by the capture as capture_<n>.gif.  Returns the path written.  Bound to the gif
+    //hotkey, f11 by default.
+    pub fn save_gif(&self, capture: &GifCapture) -> std::io::Result<String> {
+        let path = Self::next_free_path(GIF_PREFIX, "gif");
+        capture.save_gif(&path)?;
+
+        Ok(path)
+    }
+
+    //<prefix><n>.<extension> with the first number not used by a file in the working directory
+    fn next_free_path(prefix: &str, extension: &str) -> String {
+        let mut number = 1;
+        loop {
+            let path = format!("{}{:03}.{}", prefix, number, extension);
+            if !Path::new(&path).exists() {
+                return path;
+            }
+            number += 1;
+        }
     }
 
     pub fn print_log_file(&mut self, cpu: &Cpu) -> std::io::Result<()> {
@@ -71,6 +169,8 @@ impl WindowsInterface {
             for i in 0..buffer_size {
                 cpu.load_read_only_data(i, buffer[i]);
             }
+
+            cpu.init_cartridge();
         } else {
             println!("Rom size ({} bytes) greater than end of vram.", buffer_size);
         }
@@ -0,0 +1,568 @@
+use crate::apu_channels::*;
+use crate::audio_sink::{self, AudioSink};
+use crate::cpu::CPU_CLOCK_HZ;
+use crate::resampler::Resampler;
+use std::io;
+use std::vec::Vec;
+
+//Audio processing unit: https://gbdev.io/pandocs/Audio.html
+
+pub const APU_ADDR_START: usize = 0xff10;
+pub const APU_ADDR_END: usize = 0xff26;
+
+const MEM_SIZE: usize = APU_ADDR_END - APU_ADDR_START + 1;
+
+pub const NR10_REG: usize = 0xff10; //Channel 1 sweep
+pub const NR14_REG: usize = 0xff14; //Channel 1 frequency high and control
+pub const NR21_REG: usize = 0xff16; //Channel 2 duty and length
+pub const NR24_REG: usize = 0xff19; //Channel 2 frequency high and control
+pub const NR30_REG: usize = 0xff1a; //Channel 3 DAC enable
+pub const NR34_REG: usize = 0xff1e; //Channel 3 frequency high and control
+pub const NR41_REG: usize = 0xff20; //Channel 4 length
+pub const NR44_REG: usize = 0xff23; //Channel 4 control
+pub const NR50_REG: usize = 0xff24; //Master volume
+pub const NR51_REG: usize = 0xff25; //Panning
+pub const NR52_REG: usize = 0xff26; //Sound on/off and channel status
+
+pub const WAVE_RAM_START: usize = 0xff30;
+pub const WAVE_RAM_END: usize = WAVE_RAM_START + WAVE_RAM_SIZE - 1;
+
+//Bits that read back as 1, write only bits included: https://gbdev.io/pandocs/Audio_Registers.html
+const READ_MASKS: [u8; MEM_SIZE] = [
+    0x80, 0x3f, 0x00, 0xff, 0xbf, //NR10-NR14
+    0xff, 0x3f, 0x00, 0xff, 0xbf, //unused, NR21-NR24
+    0x7f, 0xff, 0x9f, 0xff, 0xbf, //NR30-NR34
+    0xff, 0xff, 0x00, 0x00, 0xbf, //unused, NR41-NR44
+    0x00, 0x00, 0x70, //NR50-NR52
+];
+
+const NR52_POWER: u8 = 0x80;
+const NR52_CHANNEL_1_ON: u8 = 0x01;
+const NR52_CHANNEL_2_ON: u8 = 0x02;
+const NR52_CHANNEL_3_ON: u8 = 0x04;
+const NR52_CHANNEL_4_ON: u8 = 0x08;
+
+//The frame sequencer steps when bit 4 of DIV falls, 512 times per second
+const DIV_FRAME_SEQUENCER_BIT: u8 = 0x10;
+const FRAME_SEQUENCER_STEPS: u8 = 8;
+
+//NR50 holds the left volume in bits 6-4 and the right volume in bits 2-0, both 0-7 for 1/8 to 8/8
+const NR50_LEFT_VOLUME_SHIFT: u8 = 4;
+const NR50_VOLUME_MASK: u8 = 0x07;
+//NR51 bit n sends channel n + 1 to the right, bit n + 4 to the left
+const NR51_LEFT_SHIFT: u8 = 4;
+
+//The channels are mixed once per M-cycle
+const MIX_CYCLES: u32 = 4;
+const MIX_RATE: u32 = 1_048_576;
+const CHANNELS: usize = 4;
+
+pub const DEFAULT_SAMPLE_RATE: u32 = 48000;
+const MAX_BUFFERED_SECONDS: u32 = 1; //Older samples are dropped when nothing takes them
+
+//The capacitor of the high-pass filter keeps this much of its charge every T-cycle
+const CAPACITOR_CHARGE: f64 = 0.999958;
+
+pub struct Apu {
+    memory_registers: [u8; MEM_SIZE],
+    //0xff10-0xff14: NR10-NR14 Channel 1, pulse with sweep
+    //0xff16-0xff19: NR21-NR24 Channel 2, pulse
+    //0xff1a-0xff1e: NR30-NR34 Channel 3, wave
+    //0xff20-0xff23: NR41-NR44 Channel 4, noise
+    //0xff24: NR50 Master volume
+    //0xff25: NR51 Panning
+    //0xff26: NR52 Sound on/off
+    channel_1: PulseChannel,
+    channel_2: PulseChannel,
+    channel_3: WaveChannel,
+    channel_4: NoiseChannel,
+    frame_sequencer_step: u8, //Next step of the frame sequencer
+    div_bit: bool,            //Last state of the DIV bit clocking the frame sequencer
+    mix_cycles: u32,          //T-cycles not mixed yet
+    mix_clock: u32,           //M-cycles mixed since the resamplers ended their last time frame
+    output: StereoOutput,
+    sample_rate: u32,
+    capacitor_charge: f32,
+    samples: Vec<f32>, //Stereo output not taken yet, left and right interleaved
+    audio_sink: Option<Box<dyn AudioSink>>,
+    audio_sink_error: Option<io::Error>, //The sink stops getting samples after an error
+    muted: [bool; CHANNELS],             //Only silences the mix, the channels keep running
+    channel_capture: Option<ChannelCapture>,
+}
+
+impl Apu {
+    pub fn new() -> Self {
+        let mut memory_registers = [0; MEM_SIZE];
+        memory_registers[NR52_REG - APU_ADDR_START] = NR52_POWER;
+
+        Self {
+            memory_registers,
+            channel_1: PulseChannel::new(true),
+            channel_2: PulseChannel::new(false),
+            channel_3: WaveChannel::new(),
+            channel_4: NoiseChannel::new(),
+            frame_sequencer_step: 0,
+            div_bit: false,
+            mix_cycles: 0,
+            mix_clock: 0,
+            output: StereoOutput::new(DEFAULT_SAMPLE_RATE),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+            capacitor_charge: capacitor_charge(DEFAULT_SAMPLE_RATE),
+            samples: Vec::new(),
+            audio_sink: None,
+            audio_sink_error: None,
+            muted: [false; CHANNELS],
+            channel_capture: None,
+        }
+    }
+
+    //Advances the apu by a number of T-cycles.  `div` is the timer's DIV register after the same cycles ran.
+    pub fn update_apu(&mut self, cycles: u32, div: u8) {
+        let div_bit = div & DIV_FRAME_SEQUENCER_BIT > 0;
+        if self.div_bit && !div_bit {
+            self.step_frame_sequencer();
+        }
+        self.div_bit = div_bit;
+
+        self.mix_cycles += cycles;
+        while self.mix_cycles >= MIX_CYCLES {
+            self.mix_cycles -= MIX_CYCLES;
+            self.channel_1.step(MIX_CYCLES);
+            self.channel_2.step(MIX_CYCLES);
+            self.channel_3.step(MIX_CYCLES);
+            self.channel_4.step(MIX_CYCLES);
+            self.mix();
+            self.mix_clock += 1;
+        }
+
+        self.resample();
+    }
+
+    //The DMG corrupts wave ram when channel 3 is retriggered while playing
+    pub fn set_dmg_hardware(&mut self, enabled: bool) {
+        self.channel_3.set_dmg_hardware(enabled);
+    }
+
+    //Host sample rate of the output, e.g. 44100 or 48000.  Samples not taken yet are dropped.
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.output = StereoOutput::new(sample_rate);
+        if let Some(capture) = self.channel_capture.as_mut() {
+            capture.outputs = (0..CHANNELS).map(|_| StereoOutput::new(sample_rate)).collect();
+        }
+        self.mix_clock = 0;
+        self.capacitor_charge = capacitor_charge(sample_rate);
+        self.samples.clear();
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    //Stereo samples produced since the last call, left and right interleaved, -1.0 to 1.0
+    pub fn take_samples_f32(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples)
+    }
+
+    //Same as take_samples_f32 as 16 bit PCM
+    pub fn take_samples_i16(&mut self) -> Vec<i16> {
+        self.take_samples_f32().into_iter().map(audio_sink::to_i16).collect()
+    }
+
+    //Every batch of samples is also pushed to the sink as soon as it's ready
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.audio_sink = Some(sink);
+        self.audio_sink_error = None;
+    }
+
+    //Removes the sink and finishes it.  Returns the first error the sink had.
+    pub fn finish_audio_sink(&mut self) -> io::Result<()> {
+        let sink = self.audio_sink.take();
+        if let Some(error) = self.audio_sink_error.take() {
+            return Err(error);
+        }
+
+        match sink {
+            Some(mut sink) => sink.finish(),
+            None => Ok(()),
+        }
+    }
+
+    //Takes a channel (1-4) out of the mix or puts it back.  The channel's registers and timing are not affected.
+    pub fn set_channel_muted(&mut self, channel: usize, muted: bool) {
+        if (1..=CHANNELS).contains(&channel) {
+            self.muted[channel - 1] = muted;
+        }
+    }
+
+    pub fn channel_muted(&self, channel: usize) -> bool {
+        (1..=CHANNELS).contains(&channel) && self.muted[channel - 1]
+    }
+
+    //Mutes every channel but one, or unmutes them all with None
+    pub fn solo_channel(&mut self, channel: Option<usize>) {
+        for (index, muted) in self.muted.iter_mut().enumerate() {
+            *muted = channel.is_some_and(|solo| solo != index + 1);
+        }
+    }
+
+    //Sends each channel on its own to one of 4 sinks, panned and with the master volume like in the mix.  Mute and
+    //solo don't apply, so the tracks always add up to the full mix.
+    pub fn set_channel_sinks(&mut self, sinks: Vec<Box<dyn AudioSink>>) {
+        self.channel_capture = Some(ChannelCapture {
+            outputs: (0..CHANNELS).map(|_| StereoOutput::new(self.sample_rate)).collect(),
+            sinks,
+            batch: Vec::new(),
+            error: None,
+        });
+    }
+
+    //Stops the channel capture and finishes its sinks.  Returns the first error of any of them.
+    pub fn finish_channel_sinks(&mut self) -> io::Result<()> {
+        let capture = match self.channel_capture.take() {
+            Some(capture) => capture,
+            None => return Ok(()),
+        };
+        if let Some(error) = capture.error {
+            return Err(error);
+        }
+
+        for mut sink in capture.sinks {
+            sink.finish()?;
+        }
+
+        Ok(())
+    }
+
+    //Digital output (0-15) of a channel, 1 based like the register names.  Channels that are off output 0.
+    pub fn channel_output(&self, channel: usize) -> u8 {
+        match channel {
+            1 => self.channel_1.output(),
+            2 => self.channel_2.output(),
+            3 => self.channel_3.output(),
+            4 => self.channel_4.output(),
+            _ => 0,
+        }
+    }
+
+    //The DAC of a channel turns its digital output into -1.0 to 1.0.  A disabled DAC outputs 0.
+    pub fn channel_dac_output(&self, channel: usize) -> f32 {
+        let dac_enabled = match channel {
+            1 => self.channel_1.dac_enabled(),
+            2 => self.channel_2.dac_enabled(),
+            3 => self.channel_3.dac_enabled(),
+            4 => self.channel_4.dac_enabled(),
+            _ => false,
+        };
+
+        if dac_enabled {
+            1.0 - self.channel_output(channel) as f32 / 7.5
+        } else {
+            0.0
+        }
+    }
+
+    pub fn write_register(&mut self, index: usize, data: u8) {
+        //Only NR52 can be written while the apu is off
+        if !self.powered() && index != NR52_REG {
+            return;
+        }
+
+        let length_clocked_last = self.length_clocked_last();
+        match index {
+            NR10_REG..=NR14_REG => self
+                .channel_1
+                .write_register(index - NR10_REG, data, length_clocked_last),
+            NR21_REG..=NR24_REG => self
+                .channel_2
+                .write_register(index - NR21_REG + 1, data, length_clocked_last),
+            NR30_REG..=NR34_REG => self
+                .channel_3
+                .write_register(index - NR30_REG, data, length_clocked_last),
+            NR41_REG..=NR44_REG => self
+                .channel_4
+                .write_register(index - NR41_REG + 1, data, length_clocked_last),
+            NR52_REG => {
+                let powered = data & NR52_POWER > 0;
+                if self.powered() && !powered {
+                    self.power_off();
+                } else if !self.powered() && powered {
+                    //The frame sequencer starts over at step 0
+                    self.frame_sequencer_step = 0;
+                }
+
+                //Only the power bit can be written, the channel bits are read only
+                self.memory_registers[NR52_REG - APU_ADDR_START] = data & NR52_POWER;
+                return;
+            }
+            _ => {}
+        }
+
+        self.memory_registers[index - APU_ADDR_START] = data;
+    }
+
+    pub fn read_register(&self, index: usize) -> u8 {
+        let offset = index - APU_ADDR_START;
+        let mut value = self.memory_registers[offset];
+
+        if index == NR52_REG {
+            if self.channel_1.enabled() {
+                value |= NR52_CHANNEL_1_ON;
+            }
+            if self.channel_2.enabled() {
+                value |= NR52_CHANNEL_2_ON;
+            }
+            if self.channel_3.enabled() {
+                value |= NR52_CHANNEL_3_ON;
+            }
+            if self.channel_4.enabled() {
+                value |= NR52_CHANNEL_4_ON;
+            }
+        }
+
+        value | READ_MASKS[offset]
+    }
+
+    //Register writes that bring a powered on apu to the current settings, e.g. to start a log of the sound writes
+    //part way through a game.  Channels that are playing are retriggered.
+    pub fn register_writes(&self) -> Vec<(usize, u8)> {
+        let register = |index: usize| self.memory_registers[index - APU_ADDR_START];
+        let mut writes = vec![
+            (NR52_REG, register(NR52_REG)),
+            (NR50_REG, register(NR50_REG)),
+            (NR51_REG, register(NR51_REG)),
+            (NR30_REG, 0x00), //Wave ram can only be written freely while channel 3 is off
+        ];
+
+        for (offset, data) in self.channel_3.wave_ram().iter().enumerate() {
+            writes.push((WAVE_RAM_START + offset, *data));
+        }
+
+        let channels = [
+            (NR10_REG, NR14_REG, self.channel_1.enabled()),
+            (NR21_REG, NR24_REG, self.channel_2.enabled()),
+            (NR30_REG, NR34_REG, self.channel_3.enabled()),
+            (NR41_REG, NR44_REG, self.channel_4.enabled()),
+        ];
+        for (first, control, enabled) in channels {
+            for index in first..control {
+                writes.push((index, register(index)));
+            }
+            let trigger = if enabled { TRIGGER } else { 0 };
+            writes.push((control, (register(control) & !TRIGGER) | trigger));
+        }
+
+        writes
+    }
+
+    //Wave ram is not cleared or locked when the apu is off
+    pub fn read_wave_ram(&self, index: usize) -> u8 {
+        self.channel_3.read_wave_ram(index - WAVE_RAM_START)
+    }
+
+    pub fn write_wave_ram(&mut self, index: usize, data: u8) {
+        self.channel_3.write_wave_ram(index - WAVE_RAM_START, data);
+    }
+}
+
+//Private methods
+impl Apu {
+    //Step 0: length, 2: length and sweep, 4: length, 6: length and sweep, 7: envelope
+    fn step_frame_sequencer(&mut self) {
+        match self.frame_sequencer_step {
+            0 | 4 => self.clock_lengths(),
+            2 | 6 => {
+                self.clock_lengths();
+                self.channel_1.clock_sweep();
+            }
+            7 => {
+                self.channel_1.clock_envelope();
+                self.channel_2.clock_envelope();
+                self.channel_4.clock_envelope();
+            }
+            _ => {}
+        }
+
+        self.frame_sequencer_step = (self.frame_sequencer_step + 1) % FRAME_SEQUENCER_STEPS;
+    }
+
+    fn clock_lengths(&mut self) {
+        self.channel_1.clock_length();
+        self.channel_2.clock_length();
+        self.channel_3.clock_length();
+        self.channel_4.clock_length();
+    }
+
+    //True when the next frame sequencer step does not clock the length counters
+    fn length_clocked_last(&self) -> bool {
+        self.frame_sequencer_step % 2 == 1
+    }
+
+    fn powered(&self) -> bool {
+        self.memory_registers[NR52_REG - APU_ADDR_START] & NR52_POWER > 0
+    }
+
+    //Turning the apu off clears NR10-NR51 and silences the channels.  Wave ram keeps its contents.
+    fn power_off(&mut self) {
+        for register in &mut self.memory_registers[..NR52_REG - APU_ADDR_START] {
+            *register = 0;
+        }
+        self.channel_1 = PulseChannel::new(true);
+        self.channel_2 = PulseChannel::new(false);
+        self.channel_3.power_off();
+        self.channel_4 = NoiseChannel::new();
+    }
+
+    //Mixes the DAC outputs with the panning and master volume, and hands the changes of level to the resamplers
+    fn mix(&mut self) {
+        let panning = self.memory_registers[NR51_REG - APU_ADDR_START];
+        let volume = self.memory_registers[NR50_REG - APU_ADDR_START];
+        let left_volume = ((volume >> NR50_LEFT_VOLUME_SHIFT) & NR50_VOLUME_MASK) as f32 + 1.0;
+        let right_volume = (volume & NR50_VOLUME_MASK) as f32 + 1.0;
+
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for channel in 0..CHANNELS {
+            let output = self.channel_dac_output(channel + 1) / CHANNELS as f32;
+            let mut channel_left = 0.0;
+            let mut channel_right = 0.0;
+            if panning & (1 << (channel as u8 + NR51_LEFT_SHIFT)) > 0 {
+                channel_left = output * left_volume / 8.0;
+            }
+            if panning & (1 << channel) > 0 {
+                channel_right = output * right_volume / 8.0;
+            }
+
+            if !self.muted[channel] {
+                left += channel_left;
+                right += channel_right;
+            }
+            if let Some(capture) = self.channel_capture.as_mut() {
+                capture.outputs[channel].set_level(self.mix_clock, channel_left, channel_right);
+            }
+        }
+
+        self.output.set_level(self.mix_clock, left, right);
+    }
+
+    //Collects the finished output samples and runs them through the high-pass filter
+    fn resample(&mut self) {
+        //The capacitor only charges while a DAC is on, otherwise the output is silent
+        let dacs_enabled = [
+            self.channel_1.dac_enabled(),
+            self.channel_2.dac_enabled(),
+            self.channel_3.dac_enabled(),
+            self.channel_4.dac_enabled(),
+        ];
+
+        let batch_start = self.samples.len();
+        self.output.end_frame(
+            self.mix_clock,
+            dacs_enabled.contains(&true),
+            self.capacitor_charge,
+            &mut self.samples,
+        );
+
+        if let Some(capture) = self.channel_capture.as_mut() {
+            for (channel, output) in capture.outputs.iter_mut().enumerate() {
+                capture.batch.clear();
+                output.end_frame(
+                    self.mix_clock,
+                    dacs_enabled[channel],
+                    self.capacitor_charge,
+                    &mut capture.batch,
+                );
+                if capture.error.is_none() && !capture.batch.is_empty() {
+                    if let Err(error) = capture.sinks[channel].push_samples(&capture.batch) {
+                        capture.error = Some(error);
+                    }
+                }
+            }
+        }
+        self.mix_clock = 0;
+
+        if let Some(sink) = self.audio_sink.as_mut() {
+            if self.audio_sink_error.is_none() && self.samples.len() > batch_start {
+                if let Err(error) = sink.push_samples(&self.samples[batch_start..]) {
+                    self.audio_sink_error = Some(error);
+                }
+            }
+        }
+
+        let max_samples = (self.sample_rate * MAX_BUFFERED_SECONDS) as usize * 2;
+        if self.samples.len() > max_samples {
+            let dropped = self.samples.len() - max_samples;
+            self.samples.drain(..dropped);
+        }
+    }
+}
+
+//A stereo signal resampled to the host's rate and run through the high-pass filter
+struct StereoOutput {
+    resampler_left: Resampler,
+    resampler_right: Resampler,
+    level_left: f32, //Last levels, -1.0 to 1.0
+    level_right: f32,
+    resampled_left: Vec<f32>,
+    resampled_right: Vec<f32>,
+    capacitor_left: f32,
+    capacitor_right: f32,
+}
+
+impl StereoOutput {
+    fn new(sample_rate: u32) -> Self {
+        Self {
+            resampler_left: Resampler::new(MIX_RATE, sample_rate),
+            resampler_right: Resampler::new(MIX_RATE, sample_rate),
+            level_left: 0.0,
+            level_right: 0.0,
+            resampled_left: Vec::new(),
+            resampled_right: Vec::new(),
+            capacitor_left: 0.0,
+            capacitor_right: 0.0,
+        }
+    }
+
+    //Sets the levels from `clock` M-cycles after the start of the time frame on
+    fn set_level(&mut self, clock: u32, left: f32, right: f32) {
+        if left != self.level_left {
+            self.resampler_left.add_delta(clock, left - self.level_left);
+            self.level_left = left;
+        }
+        if right != self.level_right {
+            self.resampler_right.add_delta(clock, right - self.level_right);
+            self.level_right = right;
+        }
+    }
+
+    //Ends the time frame after `clocks` M-cycles and appends the finished samples, left and right interleaved
+    fn end_frame(&mut self, clocks: u32, dacs_enabled: bool, capacitor_charge: f32, output: &mut Vec<f32>) {
+        self.resampler_left.end_frame(clocks, &mut self.resampled_left);
+        self.resampler_right.end_frame(clocks, &mut self.resampled_right);
+
+        for (left, right) in self.resampled_left.drain(..).zip(self.resampled_right.drain(..)) {
+            let (mut out_left, mut out_right) = (0.0, 0.0);
+            if dacs_enabled {
+                out_left = left - self.capacitor_left;
+                self.capacitor_left = left - out_left * capacitor_charge;
+                out_right = right - self.capacitor_right;
+                self.capacitor_right = right - out_right * capacitor_charge;
+            }
+            output.push(out_left);
+            output.push(out_right);
+        }
+    }
+}
+
+//Each channel resampled on its own for the per channel sinks
+struct ChannelCapture {
+    outputs: Vec<StereoOutput>,
+    sinks: Vec<Box<dyn AudioSink>>,
+    batch: Vec<f32>,
+    error: Option<io::Error>, //The sinks stop getting samples after an error
+}
+
+//Charge the capacitor keeps between two output samples
+fn capacitor_charge(sample_rate: u32) -> f32 {
+    CAPACITOR_CHARGE.powf(CPU_CLOCK_HZ as f64 / sample_rate as f64) as f32
+}
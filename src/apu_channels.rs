@@ -0,0 +1,665 @@
+//Sound channels of the apu: https://gbdev.io/pandocs/Audio_details.html
+//Each channel outputs a digital value 0-15 which the apu's DACs turn into an analog level.
+
+const MAX_FREQUENCY: u16 = 2047;
+const MAX_VOLUME: u8 = 15;
+
+//NRx1
+const DUTY_SHIFT: u8 = 6;
+const PULSE_LENGTH_MASK: u8 = 0x3f;
+const PULSE_MAX_LENGTH: u16 = 64;
+
+//NRx2
+const ENVELOPE_VOLUME_SHIFT: u8 = 4;
+const ENVELOPE_INCREASE: u8 = 0x08;
+const ENVELOPE_PERIOD_MASK: u8 = 0x07;
+const DAC_ENABLE_MASK: u8 = 0xf8; //The DAC is off when the volume and direction bits are all 0
+
+//NRx4
+pub const TRIGGER: u8 = 0x80;
+pub const LENGTH_ENABLE: u8 = 0x40;
+const FREQUENCY_HIGH_MASK: u8 = 0x07;
+
+//NR10
+const SWEEP_PERIOD_SHIFT: u8 = 4;
+const SWEEP_PERIOD_MASK: u8 = 0x07;
+const SWEEP_NEGATE: u8 = 0x08;
+const SWEEP_SHIFT_MASK: u8 = 0x07;
+
+const DUTY_WAVEFORMS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1], //12.5%
+    [1, 0, 0, 0, 0, 0, 0, 1], //25%
+    [1, 0, 0, 0, 0, 1, 1, 1], //50%
+    [0, 1, 1, 1, 1, 1, 1, 0], //75%
+];
+
+//Silences the channel once it runs out.  Clocked at 256 Hz by the frame sequencer.
+#[derive(Copy, Clone)]
+pub struct LengthCounter {
+    counter: u16,
+    max_length: u16,
+    enabled: bool,
+}
+
+impl LengthCounter {
+    pub fn new(max_length: u16) -> Self {
+        Self {
+            counter: 0,
+            max_length,
+            enabled: false,
+        }
+    }
+
+    pub fn load(&mut self, length: u16) {
+        self.counter = self.max_length - length;
+    }
+
+    //Returns false when the counter expires and the channel has to be disabled
+    pub fn clock(&mut self) -> bool {
+        if self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            return self.counter > 0;
+        }
+
+        true
+    }
+
+    //Handles the length enable bit and trigger of NRx4.  Returns false if the channel has to be disabled.
+    //Enabling the counter in the first half of a length period clocks it once more: https://gbdev.io/pandocs/Audio_details.html#obscure-behavior
+    pub fn write_control(&mut self, value: u8, length_clocked_last: bool) -> bool {
+        let was_enabled = self.enabled;
+        self.enabled = value & LENGTH_ENABLE > 0;
+        let mut channel_on = true;
+
+        if length_clocked_last && !was_enabled && self.enabled && self.counter > 0 {
+            self.counter -= 1;
+            if self.counter == 0 && value & TRIGGER == 0 {
+                channel_on = false;
+            }
+        }
+
+        if value & TRIGGER > 0 && self.counter == 0 {
+            self.counter = self.max_length;
+            if length_clocked_last && self.enabled {
+                self.counter -= 1;
+            }
+        }
+
+        channel_on
+    }
+}
+
+//Changes the volume at 64 Hz
+#[derive(Copy, Clone)]
+pub struct Envelope {
+    register: u8, //NRx2
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Self {
+        Self {
+            register: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.register & DAC_ENABLE_MASK > 0
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.register >> ENVELOPE_VOLUME_SHIFT;
+        self.timer = self.period();
+    }
+
+    pub fn clock(&mut self) {
+        if self.period() == 0 {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+        if self.timer == 0 {
+            self.timer = self.period();
+            if self.register & ENVELOPE_INCREASE > 0 && self.volume < MAX_VOLUME {
+                self.volume += 1;
+            } else if self.register & ENVELOPE_INCREASE == 0 && self.volume > 0 {
+                self.volume -= 1;
+            }
+        }
+    }
+
+    fn period(&self) -> u8 {
+        self.register & ENVELOPE_PERIOD_MASK
+    }
+}
+
+//Frequency sweep of channel 1.  Clocked at 128 Hz.
+#[derive(Copy, Clone)]
+struct Sweep {
+    register: u8, //NR10
+    shadow_frequency: u16,
+    timer: u8,
+    enabled: bool,
+    negate_used: bool, //A calculation was made in negate mode since the last trigger
+}
+
+impl Sweep {
+    fn new() -> Self {
+        Self {
+            register: 0,
+            shadow_frequency: 0,
+            timer: 0,
+            enabled: false,
+            negate_used: false,
+        }
+    }
+
+    fn period(&self) -> u8 {
+        (self.register >> SWEEP_PERIOD_SHIFT) & SWEEP_PERIOD_MASK
+    }
+
+    fn shift(&self) -> u8 {
+        self.register & SWEEP_SHIFT_MASK
+    }
+
+    //A period of 0 reloads the timer with 8
+    fn reload_timer(&mut self) {
+        self.timer = if self.period() == 0 { 8 } else { self.period() };
+    }
+
+    //Returns the next frequency, None when it overflows and the channel has to be disabled
+    fn calculate(&mut self) -> Option<u16> {
+        let delta = self.shadow_frequency >> self.shift();
+        let frequency = if self.register & SWEEP_NEGATE > 0 {
+            self.negate_used = true;
+            self.shadow_frequency - delta
+        } else {
+            self.shadow_frequency + delta
+        };
+
+        if frequency > MAX_FREQUENCY {
+            None
+        } else {
+            Some(frequency)
+        }
+    }
+}
+
+//Square wave channels 1 and 2.  Only channel 1 has the frequency sweep.
+pub struct PulseChannel {
+    enabled: bool,
+    has_sweep: bool,
+    duty: u8,
+    duty_step: u8,
+    frequency: u16,
+    frequency_timer: u16, //T-cycles until the next duty step
+    length: LengthCounter,
+    envelope: Envelope,
+    sweep: Sweep,
+}
+
+impl PulseChannel {
+    pub fn new(has_sweep: bool) -> Self {
+        Self {
+            enabled: false,
+            has_sweep,
+            duty: 0,
+            duty_step: 0,
+            frequency: 0,
+            frequency_timer: 0,
+            length: LengthCounter::new(PULSE_MAX_LENGTH),
+            envelope: Envelope::new(),
+            sweep: Sweep::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    //Digital output 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+
+        DUTY_WAVEFORMS[self.duty as usize][self.duty_step as usize] * self.envelope.volume()
+    }
+
+    //Advances the waveform by a number of T-cycles
+    pub fn step(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if self.frequency_timer as u32 > cycles {
+                self.frequency_timer -= cycles as u16;
+                return;
+            }
+
+            cycles -= self.frequency_timer as u32;
+            self.frequency_timer = self.period();
+            self.duty_step = (self.duty_step + 1) % 8;
+        }
+    }
+
+    //Writes one of the channel's registers.  `register` is 0-4 for NRx0-NRx4.
+    //`length_clocked_last` is true when the frame sequencer's last step clocked the length counters.
+    pub fn write_register(&mut self, register: usize, value: u8, length_clocked_last: bool) {
+        match register {
+            0 => {
+                //Leaving negate mode after it was used for a calculation disables the channel
+                if self.sweep.register & SWEEP_NEGATE > 0 && value & SWEEP_NEGATE == 0 && self.sweep.negate_used {
+                    self.enabled = false;
+                }
+                self.sweep.register = value;
+            }
+            1 => {
+                self.duty = value >> DUTY_SHIFT;
+                self.length.load((value & PULSE_LENGTH_MASK) as u16);
+            }
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xff) | (((value & FREQUENCY_HIGH_MASK) as u16) << 8);
+                if !self.length.write_control(value, length_clocked_last) {
+                    self.enabled = false;
+                }
+                if value & TRIGGER > 0 {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        if !self.has_sweep {
+            return;
+        }
+
+        if self.sweep.timer > 0 {
+            self.sweep.timer -= 1;
+        }
+        if self.sweep.timer > 0 {
+            return;
+        }
+
+        self.sweep.reload_timer();
+        if !self.sweep.enabled || self.sweep.period() == 0 {
+            return;
+        }
+
+        match self.sweep.calculate() {
+            Some(frequency) if self.sweep.shift() > 0 => {
+                self.sweep.shadow_frequency = frequency;
+                self.frequency = frequency;
+                //The new frequency is checked for overflow again but not used
+                if self.sweep.calculate().is_none() {
+                    self.enabled = false;
+                }
+            }
+            Some(_) => {}
+            None => self.enabled = false,
+        }
+    }
+}
+
+//Private methods
+impl PulseChannel {
+    //T-cycles per duty step
+    fn period(&self) -> u16 {
+        (MAX_FREQUENCY + 1 - self.frequency) * 4
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.frequency_timer = self.period();
+        self.envelope.trigger();
+
+        if self.has_sweep {
+            self.sweep.shadow_frequency = self.frequency;
+            self.sweep.reload_timer();
+            self.sweep.enabled = self.sweep.period() > 0 || self.sweep.shift() > 0;
+            self.sweep.negate_used = false;
+            if self.sweep.shift() > 0 && self.sweep.calculate().is_none() {
+                self.enabled = false;
+            }
+        }
+    }
+}
+
+//NR30
+const WAVE_DAC_ENABLE: u8 = 0x80;
+
+//NR32
+const WAVE_OUTPUT_LEVEL_SHIFT: u8 = 5;
+const WAVE_OUTPUT_LEVEL_MASK: u8 = 0x03;
+const WAVE_MAX_LENGTH: u16 = 256;
+
+pub const WAVE_RAM_SIZE: usize = 16;
+const WAVE_SAMPLES: u8 = 32; //2 samples per byte, upper nibble first
+
+//Channel 3 plays the 32 4-bit samples of wave ram
+pub struct WaveChannel {
+    enabled: bool,
+    dac_enabled: bool,
+    output_shift: u8, //Volume as a right shift of the sample: 4 (mute), 0 (100%), 1 (50%) or 2 (25%)
+    frequency: u16,
+    frequency_timer: u16,
+    position: u8,      //Sample being played, 0-31
+    sample_buffer: u8, //The last sample read.  Triggering does not refresh it.
+    length: LengthCounter,
+    wave_ram: [u8; WAVE_RAM_SIZE],
+    dmg_hardware: bool, //Retriggering corrupts wave ram on the DMG
+}
+
+impl WaveChannel {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            dac_enabled: false,
+            output_shift: 4,
+            frequency: 0,
+            frequency_timer: 0,
+            position: 0,
+            sample_buffer: 0,
+            length: LengthCounter::new(WAVE_MAX_LENGTH),
+            wave_ram: [0; WAVE_RAM_SIZE],
+            dmg_hardware: false,
+        }
+    }
+
+    pub fn set_dmg_hardware(&mut self, enabled: bool) {
+        self.dmg_hardware = enabled;
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.dac_enabled
+    }
+
+    //Digital output 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+
+        self.sample_buffer >> self.output_shift
+    }
+
+    //Advances the waveform by a number of T-cycles
+    pub fn step(&mut self, cycles: u32) {
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if self.frequency_timer as u32 > cycles {
+                self.frequency_timer -= cycles as u16;
+                return;
+            }
+
+            cycles -= self.frequency_timer as u32;
+            self.frequency_timer = self.period();
+            self.position = (self.position + 1) % WAVE_SAMPLES;
+            if self.enabled {
+                self.sample_buffer = self.sample(self.position);
+            }
+        }
+    }
+
+    //Writes one of the channel's registers.  `register` is 0-4 for NR30-NR34.
+    pub fn write_register(&mut self, register: usize, value: u8, length_clocked_last: bool) {
+        match register {
+            0 => {
+                self.dac_enabled = value & WAVE_DAC_ENABLE > 0;
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(value as u16),
+            2 => {
+                self.output_shift = match (value >> WAVE_OUTPUT_LEVEL_SHIFT) & WAVE_OUTPUT_LEVEL_MASK {
+                    0 => 4,
+                    level => level - 1,
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | value as u16,
+            4 => {
+                self.frequency = (self.frequency & 0xff) | (((value & FREQUENCY_HIGH_MASK) as u16) << 8);
+                if !self.length.write_control(value, length_clocked_last) {
+                    self.enabled = false;
+                }
+                if value & TRIGGER > 0 {
+                    self.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    //While the channel plays, the cpu can only reach the byte the channel is reading
+    pub fn read_wave_ram(&self, offset: usize) -> u8 {
+        if self.enabled {
+            self.wave_ram[self.position as usize / 2]
+        } else {
+            self.wave_ram[offset]
+        }
+    }
+
+    //Wave ram as stored, without the access restrictions of read_wave_ram
+    pub fn wave_ram(&self) -> [u8; WAVE_RAM_SIZE] {
+        self.wave_ram
+    }
+
+    pub fn write_wave_ram(&mut self, offset: usize, value: u8) {
+        if self.enabled {
+            self.wave_ram[self.position as usize / 2] = value;
+        } else {
+            self.wave_ram[offset] = value;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    //Resets the channel when the apu is turned off.  Wave ram is kept.
+    pub fn power_off(&mut self) {
+        *self = Self {
+            wave_ram: self.wave_ram,
+            dmg_hardware: self.dmg_hardware,
+            ..Self::new()
+        };
+    }
+}
+
+//Private methods
+impl WaveChannel {
+    //T-cycles per sample
+    fn period(&self) -> u16 {
+        (MAX_FREQUENCY + 1 - self.frequency) * 2
+    }
+
+    fn sample(&self, position: u8) -> u8 {
+        let byte = self.wave_ram[position as usize / 2];
+        if position & 1 == 0 {
+            byte >> 4
+        } else {
+            byte & 0x0f
+        }
+    }
+
+    fn trigger(&mut self) {
+        //On the DMG, retriggering right as the channel reads the next byte overwrites the start of wave ram with
+        //the block of 4 bytes holding that byte, or just the byte when it is in the first block
+        if self.dmg_hardware && self.enabled && self.frequency_timer == 2 {
+            let next_byte = ((self.position + 1) % WAVE_SAMPLES) as usize / 2;
+            if next_byte < 4 {
+                self.wave_ram[0] = self.wave_ram[next_byte];
+            } else {
+                let block = next_byte & !3;
+                self.wave_ram.copy_within(block..block + 4, 0);
+            }
+        }
+
+        self.enabled = self.dac_enabled;
+        self.position = 0;
+        //The first sample is read a little later than a normal step
+        self.frequency_timer = self.period() + 6;
+    }
+}
+
+//NR43
+const NOISE_CLOCK_SHIFT: u8 = 4;
+const NOISE_SHORT_WIDTH: u8 = 0x08;
+const NOISE_DIVISOR_MASK: u8 = 0x07;
+const NOISE_MAX_LENGTH: u16 = 64;
+const NOISE_LENGTH_MASK: u8 = 0x3f;
+const NOISE_STOPPED_SHIFT: u8 = 14; //Clock shifts 14 and 15 stop the LFSR
+
+//Channel 4 outputs the low bit of a linear feedback shift register
+pub struct NoiseChannel {
+    enabled: bool,
+    lfsr: u16,
+    register: u8, //NR43
+    frequency_timer: u32,
+    length: LengthCounter,
+    envelope: Envelope,
+}
+
+impl NoiseChannel {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            lfsr: 0,
+            register: 0,
+            frequency_timer: 0,
+            length: LengthCounter::new(NOISE_MAX_LENGTH),
+            envelope: Envelope::new(),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn dac_enabled(&self) -> bool {
+        self.envelope.dac_enabled()
+    }
+
+    //Digital output 0-15
+    pub fn output(&self) -> u8 {
+        if !self.enabled {
+            return 0;
+        }
+
+        (!self.lfsr & 1) as u8 * self.envelope.volume()
+    }
+
+    //Advances the LFSR by a number of T-cycles
+    pub fn step(&mut self, cycles: u32) {
+        if self.register >> NOISE_CLOCK_SHIFT >= NOISE_STOPPED_SHIFT {
+            return;
+        }
+
+        let mut cycles = cycles;
+        while cycles > 0 {
+            if self.frequency_timer > cycles {
+                self.frequency_timer -= cycles;
+                return;
+            }
+
+            cycles -= self.frequency_timer;
+            self.frequency_timer = self.period();
+
+            let feedback = (self.lfsr ^ (self.lfsr >> 1)) & 1;
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+            if self.register & NOISE_SHORT_WIDTH > 0 {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+            }
+        }
+    }
+
+    //Writes one of the channel's registers.  `register` is 1-4 for NR41-NR44.
+    pub fn write_register(&mut self, register: usize, value: u8, length_clocked_last: bool) {
+        match register {
+            1 => self.length.load((value & NOISE_LENGTH_MASK) as u16),
+            2 => {
+                self.envelope.write(value);
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.register = value,
+            4 => {
+                if !self.length.write_control(value, length_clocked_last) {
+                    self.enabled = false;
+                }
+                if value & TRIGGER > 0 {
+                    self.enabled = self.envelope.dac_enabled();
+                    self.lfsr = 0x7fff;
+                    self.frequency_timer = self.period();
+                    self.envelope.trigger();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if !self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+}
+
+//Private methods
+impl NoiseChannel {
+    //T-cycles between LFSR shifts.  A divisor code of 0 is treated as 0.5.
+    fn period(&self) -> u32 {
+        let divisor = match self.register & NOISE_DIVISOR_MASK {
+            0 => 8,
+            code => code as u32 * 16,
+        };
+
+        divisor << (self.register >> NOISE_CLOCK_SHIFT)
+    }
+}
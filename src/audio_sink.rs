@@ -0,0 +1,221 @@
+use std::io;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::vec::Vec;
+
+use crate::wav::WavWriter;
+
+//Destinations for the apu's output.  The apu pushes each batch of stereo samples as it finishes them.
+
+pub trait AudioSink {
+    //Stereo samples, left and right interleaved, -1.0 to 1.0
+    fn push_samples(&mut self, samples: &[f32]) -> io::Result<()>;
+
+    //Called once no more samples will be pushed
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+//Discards the samples, for running without sound
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn push_samples(&mut self, _samples: &[f32]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+//Writes the samples to a 16-bit stereo WAV file.  The output only depends on the rom and the inputs, so headless
+//runs can be checked by comparing hashes of the file.
+pub struct WavSink {
+    writer: WavWriter,
+    unflushed: u32, //Samples per channel since the header was last updated
+    flush_interval: u32,
+}
+
+impl WavSink {
+    pub fn create(path: &str, sample_rate: u32) -> io::Result<Self> {
+        Ok(Self {
+            writer: WavWriter::create(path, sample_rate, 2)?,
+            unflushed: 0,
+            flush_interval: sample_rate, //About once a second
+        })
+    }
+}
+
+impl AudioSink for WavSink {
+    fn push_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let pcm: Vec<i16> = samples.iter().map(|sample| to_i16(*sample)).collect();
+        self.writer.write_samples(&pcm)?;
+
+        self.unflushed += (samples.len() / 2) as u32;
+        if self.unflushed >= self.flush_interval {
+            self.unflushed = 0;
+            self.writer.flush()?;
+        }
+
+        Ok(())
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+pub fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+//Single producer, single consumer ring buffer between the emulator and a real-time audio callback.  Samples are
+//stored as the bits of their f32 so no locks are needed.  When the buffer is full new samples are dropped, when it
+//runs dry the reader gets fewer samples than it asked for.
+struct RingBuffer {
+    samples: Vec<AtomicU32>,
+    read: AtomicUsize,  //Total samples read
+    write: AtomicUsize, //Total samples written
+}
+
+pub struct RingBufferSink {
+    ring: Arc<RingBuffer>,
+}
+
+pub struct RingBufferReader {
+    ring: Arc<RingBuffer>,
+}
+
+//Creates the two ends of a ring buffer holding `capacity` samples (left and right counted separately)
+pub fn ring_buffer(capacity: usize) -> (RingBufferSink, RingBufferReader) {
+    let ring = Arc::new(RingBuffer {
+        samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+        read: AtomicUsize::new(0),
+        write: AtomicUsize::new(0),
+    });
+
+    (RingBufferSink { ring: ring.clone() }, RingBufferReader { ring })
+}
+
+impl AudioSink for RingBufferSink {
+    fn push_samples(&mut self, samples: &[f32]) -> io::Result<()> {
+        let capacity = self.ring.samples.len();
+        let read = self.ring.read.load(Ordering::Acquire);
+        let mut write = self.ring.write.load(Ordering::Relaxed);
+
+        //Whole stereo pairs only, so the channels don't swap when samples are dropped
+        let free = (capacity - (write - read)) & !1;
+        for sample in &samples[..samples.len().min(free)] {
+            self.ring.samples[write % capacity].store(sample.to_bits(), Ordering::Relaxed);
+            write += 1;
+        }
+        self.ring.write.store(write, Ordering::Release);
+
+        Ok(())
+    }
+}
+
+impl RingBufferReader {
+    //Fills `output` with the oldest samples and returns how many were available
+    pub fn read(&mut self, output: &mut [f32]) -> usize {
+        let capacity = self.ring.samples.len();
+        let write = self.ring.write.load(Ordering::Acquire);
+        let mut read = self.ring.read.load(Ordering::Relaxed);
+
+        let count = output.len().min(write - read);
+        for sample in &mut output[..count] {
+            *sample = f32::from_bits(self.ring.samples[read % capacity].load(Ordering::Relaxed));
+            read += 1;
+        }
+        self.ring.read.store(read, Ordering::Release);
+
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::apu::*;
+    use std::env;
+    use std::fs;
+    use std::thread;
+
+    const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn fnv1a(bytes: &[u8]) -> u64 {
+        bytes
+            .iter()
+            .fold(FNV_OFFSET, |hash, byte| (hash ^ *byte as u64).wrapping_mul(FNV_PRIME))
+    }
+
+    //A tenth of a second of channel 1 panned right and channel 2 panned left, both fading out, with the DIV
+    //register running so the envelopes step.  The hash changes whenever the mixer, the resampler or the WAV writer
+    //output changes, so update it only after checking the new output by ear.
+    #[test]
+    fn wav_output_matches_the_golden_hash() {
+        let path = env::temp_dir().join(format!("gbc_emulator_golden_{}.wav", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut apu = Apu::new();
+        apu.set_audio_sink(Box::new(WavSink::create(path, DEFAULT_SAMPLE_RATE).unwrap()));
+        apu.write_register(NR50_REG, 0x77);
+        apu.write_register(NR51_REG, 0x21);
+        apu.write_register(NR10_REG + 1, 0x80); //50% duty
+        apu.write_register(NR10_REG + 2, 0xf3); //Full volume, decreasing
+        apu.write_register(NR10_REG + 3, 0xc1);
+        apu.write_register(NR14_REG, 0x87);
+        apu.write_register(NR21_REG, 0x40);
+        apu.write_register(NR21_REG + 1, 0xa0);
+        apu.write_register(NR21_REG + 2, 0x83);
+        apu.write_register(NR24_REG, 0x87);
+
+        let mut system_counter: u16 = 0;
+        for _ in 0..crate::cpu::CPU_CLOCK_HZ / 10 / 4 {
+            system_counter = system_counter.wrapping_add(4);
+            apu.update_apu(4, (system_counter >> 8) as u8);
+        }
+        apu.finish_audio_sink().unwrap();
+
+        let wav = fs::read(path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert!(wav[44..].iter().any(|byte| *byte != 0));
+        assert_eq!(fnv1a(&wav), 0xff03_8039_f3c1_8438);
+    }
+
+    //The emulator and the audio callback run on different threads, so the reader must see every sample the sink
+    //pushed, in order, even while the two ends race each other.
+    #[test]
+    fn ring_buffer_passes_samples_between_threads() {
+        const PAIRS: usize = 10_000;
+        let (mut sink, mut reader) = ring_buffer(64);
+
+        let producer = thread::spawn(move || {
+            let mut pair = 0;
+            while pair < PAIRS {
+                let before = sink.ring.write.load(Ordering::Relaxed);
+                sink.push_samples(&[pair as f32, -(pair as f32)]).unwrap();
+                if sink.ring.write.load(Ordering::Relaxed) != before {
+                    pair += 1;
+                } else {
+                    thread::yield_now();
+                }
+            }
+        });
+
+        let mut received = Vec::new();
+        let mut output = [0.0; 16];
+        while received.len() < PAIRS * 2 {
+            let count = reader.read(&mut output);
+            received.extend_from_slice(&output[..count]);
+            if count == 0 {
+                thread::yield_now();
+            }
+        }
+        producer.join().unwrap();
+
+        for (pair, samples) in received.chunks(2).enumerate() {
+            assert_eq!(samples, [pair as f32, -(pair as f32)]);
+        }
+        assert_eq!(reader.read(&mut output), 0);
+    }
+}
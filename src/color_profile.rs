@@ -0,0 +1,156 @@
+use std::vec::Vec;
+
+//Maps the DMG's 2-bit shades and the CGB's RGB555 colors to the RGB888 colors sent to the host display
+
+const RGB555_COLORS: usize = 0x8000;
+
+//Colors for the 4 DMG shades, lightest to darkest
+#[derive(Copy, Clone, PartialEq)]
+pub enum DmgPalette {
+    ClassicGreen,
+    PocketGrey,
+    LightBacklight,
+    Greyscale,
+    Custom([u32; 4]),
+}
+
+impl DmgPalette {
+    pub fn colors(&self) -> [u32; 4] {
+        match self {
+            DmgPalette::ClassicGreen => [0x9bbc0f, 0x8bac0f, 0x306230, 0x0f380f],
+            DmgPalette::PocketGrey => [0xc4cfa1, 0x8b956d, 0x4d533c, 0x1f1f1f],
+            DmgPalette::LightBacklight => [0x00b581, 0x009a71, 0x00694a, 0x004f3b],
+            DmgPalette::Greyscale => [0xffffff, 0xaaaaaa, 0x555555, 0x000000],
+            DmgPalette::Custom(colors) => *colors,
+        }
+    }
+
+    //Parses "green", "pocket", "light", "grey" or 4 comma separated RGB hex colors such as "e0f8d0,88c070,346856,081820"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "green" => Some(DmgPalette::ClassicGreen),
+            "pocket" => Some(DmgPalette::PocketGrey),
+            "light" => Some(DmgPalette::LightBacklight),
+            "grey" => Some(DmgPalette::Greyscale),
+            custom => {
+                let mut colors = [0u32; 4];
+                let mut count = 0;
+                for color in custom.split(',') {
+                    if count == 4 {
+                        return None;
+                    }
+                    colors[count] = u32::from_str_radix(color.trim().trim_start_matches('#'), 16).ok()?;
+                    count += 1;
+                }
+
+                if count == 4 {
+                    Some(DmgPalette::Custom(colors))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+//How CGB colors are adjusted for a modern display
+#[derive(Copy, Clone, PartialEq)]
+pub enum ColorCorrection {
+    Raw,       //RGB555 scaled to RGB888.  Colors look more saturated than on the real screen.
+    GammaLcd,  //Approximates the CGB lcd's darker gamma and color bleeding
+    GbaScreen, //Approximates a CGB game played on the GBA's screen
+}
+
+impl ColorCorrection {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "raw" => Some(ColorCorrection::Raw),
+            "lcd" => Some(ColorCorrection::GammaLcd),
+            "gba" => Some(ColorCorrection::GbaScreen),
+            _ => None,
+        }
+    }
+
+    //Gamma of the emulated screen, the channel mixing matrix, and a brightness scale
+    fn parameters(&self) -> (f32, [[f32; 3]; 3], f32) {
+        match self {
+            ColorCorrection::Raw => (1.0, [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]], 1.0),
+            ColorCorrection::GammaLcd => (
+                2.2,
+                [[0.8125, 0.125, 0.0625], [0.0, 0.75, 0.25], [0.1875, 0.125, 0.6875]],
+                0.94,
+            ),
+            ColorCorrection::GbaScreen => (
+                3.2,
+                [[0.80, 0.275, -0.075], [0.135, 0.64, 0.225], [0.195, 0.155, 0.65]],
+                0.93,
+            ),
+        }
+    }
+}
+
+const HOST_GAMMA: f32 = 2.2;
+
+pub struct ColorProfile {
+    dmg_colors: [u32; 4],
+    correction: ColorCorrection,
+    rgb555_table: Vec<u32>, //RGB888 color of every RGB555 color for the current correction
+}
+
+impl ColorProfile {
+    pub fn new() -> Self {
+        let mut profile = Self {
+            dmg_colors: DmgPalette::Greyscale.colors(),
+            correction: ColorCorrection::Raw,
+            rgb555_table: vec![0; RGB555_COLORS],
+        };
+        profile.build_table();
+
+        profile
+    }
+
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.dmg_colors = palette.colors();
+    }
+
+    pub fn set_correction(&mut self, correction: ColorCorrection) {
+        if correction != self.correction {
+            self.correction = correction;
+            self.build_table();
+        }
+    }
+
+    #[inline]
+    pub fn shade_to_rgb888(&self, shade: u8) -> u32 {
+        self.dmg_colors[(shade & 0x3) as usize]
+    }
+
+    #[inline]
+    pub fn rgb555_to_rgb888(&self, color: u16) -> u32 {
+        self.rgb555_table[(color & 0x7fff) as usize]
+    }
+
+    //Correcting every pixel is slow so all 32768 colors are converted when the correction changes
+    fn build_table(&mut self) {
+        let (screen_gamma, matrix, brightness) = self.correction.parameters();
+
+        for (color, rgb888) in self.rgb555_table.iter_mut().enumerate() {
+            let channels = [color & 0x1f, (color >> 5) & 0x1f, (color >> 10) & 0x1f];
+
+            if self.correction == ColorCorrection::Raw {
+                let [r, g, b] = channels.map(|channel| ((channel << 3) | (channel >> 2)) as u32);
+                *rgb888 = (r << 16) | (g << 8) | b;
+                continue;
+            }
+
+            let linear = channels.map(|channel| (channel as f32 / 31.0).powf(screen_gamma));
+            let mut out = 0u32;
+            for row in matrix.iter() {
+                let mixed = (row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2]) * brightness;
+                let encoded = mixed.clamp(0.0, 1.0).powf(1.0 / HOST_GAMMA);
+                out = (out << 8) | (encoded * 255.0).round() as u32;
+            }
+            *rgb888 = out;
+        }
+    }
+}
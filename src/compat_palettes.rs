@@ -0,0 +1,388 @@
+use crate::joypad::{Button, Buttons};
+
+//When a DMG only game runs on a CGB the boot rom colorizes it: https://gbdev.io/pandocs/Power_Up_Sequence.html
+//The palettes are chosen from a checksum of the title, or by holding a button combination while the boot logo shows.
+
+pub const TITLE_START: usize = 0x0134;
+pub const TITLE_END: usize = 0x0143;
+pub const NEW_LICENSEE_ADDR: usize = 0x0144;
+pub const OLD_LICENSEE_ADDR: usize = 0x014b;
+
+const NINTENDO_OLD_LICENSEE: u8 = 0x01;
+const USE_NEW_LICENSEE: u8 = 0x33;
+const NINTENDO_NEW_LICENSEE: [u8; 2] = [b'0', b'1'];
+const DISAMBIGUATION_INDEX: usize = 3; //4th letter of the title
+
+//RGB888 colors, lightest to darkest.  Each DMG shade picks one of these.
+pub type CompatColors = [u32; 4];
+
+#[derive(Copy, Clone)]
+pub struct CompatPalette {
+    pub bg: CompatColors,
+    pub obj0: CompatColors,
+    pub obj1: CompatColors,
+}
+
+//Button combinations that can be held during the boot animation
+#[derive(Copy, Clone, PartialEq)]
+pub enum ManualPalette {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+const WHITE_BROWN: CompatColors = [0xffffff, 0xffad63, 0x843100, 0x000000];
+const WHITE_RED: CompatColors = [0xffffff, 0xff8484, 0x943a3a, 0x000000];
+const WHITE_GREEN: CompatColors = [0xffffff, 0x7bff31, 0x008400, 0x000000];
+const WHITE_BLUE: CompatColors = [0xffffff, 0x63a5ff, 0x0000ff, 0x000000];
+const DARK_BROWN: CompatColors = [0xffe6c5, 0xce9c84, 0x846b29, 0x5a3108];
+const DARK_BLUE: CompatColors = [0xffffff, 0x8c8cde, 0x52528c, 0x000000];
+const GREYSCALE: CompatColors = [0xffffff, 0xa5a5a5, 0x525252, 0x000000];
+const PASTEL: CompatColors = [0xffffa5, 0xff9494, 0x9494ff, 0x000000];
+const ORANGE: CompatColors = [0xffffff, 0xffff00, 0xff0000, 0x000000];
+const YELLOW: CompatColors = [0xffffff, 0xffff00, 0x7b4a00, 0x000000];
+const GREEN: CompatColors = [0xffffff, 0x52ff00, 0xff4200, 0x000000];
+const DARK_GREEN: CompatColors = [0xffffff, 0x7bff31, 0x0063c5, 0x000000];
+const INVERTED: CompatColors = [0x000000, 0x008484, 0xffde00, 0xffffff];
+
+//Only used by the title table
+const WHITE_ORANGE: CompatColors = [0xffffff, 0xff9c00, 0xff0000, 0x000000];
+const LAVENDER: CompatColors = [0xa59cff, 0xffff00, 0x006300, 0x000000];
+const WHITE_OLIVE: CompatColors = [0xffffff, 0xadad84, 0x42737b, 0x000000];
+const WHITE_TANGERINE: CompatColors = [0xffffff, 0xff7300, 0x944200, 0x000000];
+const GOLD: CompatColors = [0xffc542, 0xffd600, 0x943a00, 0x4a0000];
+const LIME_BROWN: CompatColors = [0xffffff, 0x7bff00, 0xb57300, 0x000000];
+const SALMON: CompatColors = [0xff6352, 0xd60000, 0x630000, 0x000000];
+const SKY: CompatColors = [0xb5b5ff, 0xffff94, 0xad5a42, 0x000000];
+const INVERTED_RED: CompatColors = [0x000000, 0xffffff, 0xff8484, 0x943a3a];
+const TRICOLOR: CompatColors = [0xffffff, 0x5abdff, 0xff0000, 0x0000ff];
+const JUNGLE: CompatColors = [0xffff9c, 0x94b5ff, 0x639473, 0x003a3a];
+const FIELD: CompatColors = [0x6bff00, 0xffffff, 0xff524a, 0x000000];
+const WHITE_SKY: CompatColors = [0xffffff, 0xffffff, 0x63a5ff, 0x0000ff];
+const DIAMOND: CompatColors = [0x52de00, 0xff8400, 0xffff00, 0xffffff];
+const BLUE_SKY: CompatColors = [0x0000ff, 0xffffff, 0xffff7b, 0x0084ff];
+const CREAM: CompatColors = [0xffffce, 0x63efef, 0x9c8431, 0x5a5a5a];
+const SEA: CompatColors = [0xffffff, 0xffff7b, 0x0084ff, 0xff0000];
+const FOREST: CompatColors = [0xffffff, 0x00ff00, 0x318400, 0x004a00];
+const FIRE: CompatColors = [0xffff00, 0xff0000, 0x630000, 0x000000];
+
+impl ManualPalette {
+    pub fn palette(&self) -> CompatPalette {
+        let (bg, obj0, obj1) = match self {
+            ManualPalette::Up => (WHITE_BROWN, WHITE_BROWN, WHITE_BROWN),
+            ManualPalette::UpA => (WHITE_RED, WHITE_GREEN, WHITE_BLUE),
+            ManualPalette::UpB => (DARK_BROWN, WHITE_BROWN, WHITE_BROWN),
+            ManualPalette::Left => (WHITE_BLUE, WHITE_RED, WHITE_GREEN),
+            ManualPalette::LeftA => (DARK_BLUE, WHITE_RED, WHITE_BROWN),
+            ManualPalette::LeftB => (GREYSCALE, GREYSCALE, GREYSCALE),
+            ManualPalette::Down => (PASTEL, PASTEL, PASTEL),
+            ManualPalette::DownA => (ORANGE, ORANGE, ORANGE),
+            ManualPalette::DownB => (YELLOW, WHITE_BLUE, WHITE_GREEN),
+            ManualPalette::Right => (GREEN, GREEN, GREEN),
+            ManualPalette::RightA => (DARK_GREEN, WHITE_RED, WHITE_RED),
+            ManualPalette::RightB => (INVERTED, INVERTED, INVERTED),
+        };
+
+        CompatPalette { bg, obj0, obj1 }
+    }
+
+    //The combo made by the buttons held at boot.  A direction is needed, A wins over B when both are held.
+    pub fn from_buttons(buttons: Buttons) -> Option<Self> {
+        let (plain, with_a, with_b) = if buttons.is_pressed(Button::Up) {
+            (ManualPalette::Up, ManualPalette::UpA, ManualPalette::UpB)
+        } else if buttons.is_pressed(Button::Left) {
+            (ManualPalette::Left, ManualPalette::LeftA, ManualPalette::LeftB)
+        } else if buttons.is_pressed(Button::Down) {
+            (ManualPalette::Down, ManualPalette::DownA, ManualPalette::DownB)
+        } else if buttons.is_pressed(Button::Right) {
+            (ManualPalette::Right, ManualPalette::RightA, ManualPalette::RightB)
+        } else {
+            return None;
+        };
+
+        if buttons.is_pressed(Button::A) {
+            Some(with_a)
+        } else if buttons.is_pressed(Button::B) {
+            Some(with_b)
+        } else {
+            Some(plain)
+        }
+    }
+
+    //Parses combos written like "up", "left+a" or "right+b"
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "up" => Some(ManualPalette::Up),
+            "up+a" => Some(ManualPalette::UpA),
+            "up+b" => Some(ManualPalette::UpB),
+            "left" => Some(ManualPalette::Left),
+            "left+a" => Some(ManualPalette::LeftA),
+            "left+b" => Some(ManualPalette::LeftB),
+            "down" => Some(ManualPalette::Down),
+            "down+a" => Some(ManualPalette::DownA),
+            "down+b" => Some(ManualPalette::DownB),
+            "right" => Some(ManualPalette::Right),
+            "right+a" => Some(ManualPalette::RightA),
+            "right+b" => Some(ManualPalette::RightB),
+            _ => None,
+        }
+    }
+}
+
+//Games without an entry in the title table, including every game not published by Nintendo
+const DEFAULT_PALETTE: ManualPalette = ManualPalette::RightA;
+
+struct TitleEntry {
+    checksum: u8,
+    fourth_letter: Option<u8>, //Used when several titles share a checksum
+    palette: CompatPalette,
+}
+
+const fn title(
+    checksum: u8,
+    fourth_letter: Option<u8>,
+    bg: CompatColors,
+    obj0: CompatColors,
+    obj1: CompatColors,
+) -> TitleEntry {
+    TitleEntry {
+        checksum,
+        fourth_letter,
+        palette: CompatPalette { bg, obj0, obj1 },
+    }
+}
+
+//The boot rom's table of title checksums, in its order.  Titles sharing a checksum come last and are told apart
+//by their 4th letter.  Titles missing from this table use the default palette.
+const TITLE_TABLE: [TitleEntry; 93] = [
+    title(0x88, None, LAVENDER, LAVENDER, LAVENDER),          //ALLEY WAY
+    title(0x16, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //YAKUMAN
+    title(0x36, None, DIAMOND, WHITE_SKY, WHITE_RED),         //BASEBALL
+    title(0xd1, None, FIELD, WHITE_SKY, WHITE_BROWN),         //TENNIS
+    title(0xdb, None, ORANGE, ORANGE, ORANGE),                //TETRIS
+    title(0xf2, None, ORANGE, ORANGE, TRICOLOR),              //QIX
+    title(0x3c, None, WHITE_BLUE, WHITE_BLUE, WHITE_RED),     //DR.MARIO
+    title(0x8c, None, WHITE_OLIVE, WHITE_TANGERINE, WHITE_OLIVE), //RADARMISSION
+    title(0x92, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //F1RACE
+    title(0x3d, None, GREEN, WHITE_BROWN, WHITE_BROWN),       //YOSSY NO TAMAGO
+    title(0x5c, None, LAVENDER, SALMON, BLUE_SKY),            //HOSHINOKA-BI
+    title(0x58, None, GREYSCALE, GREYSCALE, GREYSCALE),       //X
+    title(0xc9, None, CREAM, WHITE_TANGERINE, WHITE_BLUE),    //MARIOLAND2
+    title(0x3e, None, WHITE_ORANGE, WHITE_ORANGE, TRICOLOR),  //YOSSY NO COOKIE
+    title(0x70, None, FOREST, WHITE_RED, WHITE_BLUE),         //ZELDA
+    title(0x1d, None, LAVENDER, SALMON, SALMON),              //KIRBY'S PINBALL
+    title(0x59, None, WHITE_GREEN, WHITE_RED, WHITE_BLUE),    //SUPERMARIOLAND3
+    title(0x69, None, ORANGE, ORANGE, TRICOLOR),              //TETRIS FLASH
+    title(0x19, None, WHITE_ORANGE, WHITE_BROWN, WHITE_BROWN), //DONKEY KONG
+    title(0x35, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //MARIO'S PICROSS
+    title(0xa8, None, DARK_BLUE, WHITE_RED, GOLD),            //SUPER DONKEYKONG
+    title(0x14, None, WHITE_RED, WHITE_GREEN, WHITE_RED),     //POKEMON RED
+    title(0xaa, None, DARK_GREEN, WHITE_RED, DARK_GREEN),     //POKEMON GREEN
+    title(0x75, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //PICROSS 2
+    title(0x95, None, GREEN, GREEN, TRICOLOR),                //YOSSY NO PANEPON
+    title(0x99, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //KIRAKIRA KIDS
+    title(0x34, None, LIME_BROWN, WHITE_BROWN, WHITE_BROWN),  //GAMEBOY GALLERY
+    title(0x6f, None, WHITE_RED, WHITE_GREEN, WHITE_RED),     //POCKETCAMERA
+    title(0x15, None, ORANGE, ORANGE, ORANGE),                //POKEMON YELLOW
+    title(0xff, None, WHITE_ORANGE, WHITE_ORANGE, WHITE_ORANGE), //BALLOON KID
+    title(0x97, None, WHITE_BLUE, WHITE_RED, WHITE_RED),      //KINGOFTHEZOO
+    title(0x4b, None, WHITE_GREEN, WHITE_RED, WHITE_RED),     //DMG FOOTBALL
+    title(0x90, None, WHITE_GREEN, WHITE_RED, WHITE_RED),     //WORLD CUP
+    title(0x17, None, WHITE_BLUE, WHITE_RED, WHITE_GREEN),    //OTHELLO
+    title(0x10, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //SUPER RC PRO-AM
+    title(0x39, None, WHITE_BLUE, WHITE_RED, WHITE_RED),      //DYNABLASTER
+    title(0xf7, None, DARK_GREEN, WHITE_BROWN, WHITE_BLUE),   //BOY AND BLOB GB2
+    title(0xf6, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //MEGAMAN
+    title(0xa2, None, DARK_GREEN, WHITE_BROWN, WHITE_BLUE),   //STAR WARS-NOA
+    title(0x49, None, LAVENDER, SALMON, BLUE_SKY),            //KIRBY DREAM LAND
+    title(0x4e, None, SEA, WHITE_RED, WHITE_GREEN),           //WAVERACE
+    title(0x43, None, WHITE_BLUE, WHITE_RED, WHITE_RED),      //THE CHESSMASTER
+    title(0x68, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //LOLO2
+    title(0xe0, None, WHITE_ORANGE, WHITE_ORANGE, TRICOLOR),  //YOSHI'S COOKIE
+    title(0x8b, None, WHITE_BLUE, WHITE_RED, WHITE_GREEN),    //MYSTIC QUEST
+    title(0xf0, None, FIELD, WHITE_SKY, WHITE_BROWN),         //TOPRANKTENNIS
+    title(0xce, None, FIELD, WHITE_SKY, WHITE_BROWN),         //TOPRANKINGTENNIS
+    title(0x0c, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //MANSELL
+    title(0x29, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //MEGAMAN3
+    title(0xe8, None, INVERTED, INVERTED, INVERTED),          //SPACE INVADERS
+    title(0xb7, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //GAME&WATCH
+    title(0x86, None, JUNGLE, GOLD, WHITE_RED),               //DONKEYKONGLAND95
+    title(0x9a, None, WHITE_GREEN, WHITE_RED, WHITE_RED),     //ASTEROIDS/MISCMD
+    title(0x52, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //STREET FIGHTER 2
+    title(0x01, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //DEFENDER/JOUST
+    title(0x9d, None, DARK_BLUE, WHITE_RED, WHITE_BROWN),     //KILLERINSTINCT95
+    title(0x71, None, WHITE_ORANGE, WHITE_ORANGE, WHITE_ORANGE), //TETRIS BLAST
+    title(0x9c, None, DARK_BLUE, DARK_BLUE, GOLD),            //PINOCCHIO
+    title(0xbd, None, WHITE_GREEN, WHITE_RED, WHITE_RED),     //TOY STORY
+    title(0x5d, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //BA.TOSHINDEN
+    title(0x6d, None, WHITE_BLUE, WHITE_BROWN, WHITE_GREEN),  //NETTOU KOF 95
+    title(0x67, None, WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //STAR STACKER
+    title(0x3f, None, DARK_GREEN, WHITE_RED, WHITE_RED),      //TETRIS PLUS
+    title(0x6b, None, DARK_BLUE, WHITE_RED, GOLD),            //DONKEYKONGLAND 3
+    title(0xb3, Some(b'B'), LAVENDER, SALMON, BLUE_SKY),      //KIRBY2
+    title(0x46, Some(b'E'), SKY, INVERTED_RED, INVERTED_RED), //SUPER MARIOLAND
+    title(0x28, Some(b'F'), WHITE_GREEN, WHITE_RED, WHITE_RED), //GOLF
+    title(0xa5, Some(b'A'), INVERTED, INVERTED, INVERTED),    //SOLARSTRIKER
+    title(0xc6, Some(b'A'), WHITE_GREEN, WHITE_RED, WHITE_BLUE), //GBWARS
+    title(0xd3, Some(b'R'), DARK_BLUE, WHITE_RED, DARK_BLUE), //KAERUNOTAMENI
+    title(0x27, Some(b'B'), LAVENDER, SALMON, BLUE_SKY),      //KIRBY BLOCKBALL
+    title(0x61, Some(b'E'), WHITE_BLUE, WHITE_RED, WHITE_BLUE), //POKEMON BLUE
+    title(0x18, Some(b'K'), DARK_BLUE, WHITE_RED, GOLD),      //DONKEYKONGLAND
+    title(0x66, Some(b'E'), LIME_BROWN, WHITE_BROWN, WHITE_BROWN), //GAMEBOY GALLERY2
+    title(0x6a, Some(b'K'), DARK_BLUE, WHITE_RED, GOLD),      //DONKEYKONGLAND 2
+    title(0xbf, Some(b' '), DARK_BLUE, WHITE_RED, WHITE_RED), //KID ICARUS
+    title(0x0d, Some(b'R'), ORANGE, ORANGE, TRICOLOR),        //TETRIS2
+    title(0xf4, Some(b'-'), DARK_GREEN, WHITE_RED, WHITE_RED), //PAC-IN-TIME
+    title(0xb3, Some(b'U'), WHITE_BROWN, WHITE_BROWN, WHITE_BROWN), //MOGURANYA
+    title(0x46, Some(b'R'), WHITE_BLUE, FIRE, WHITE_GREEN),   //METROID2
+    title(0x28, Some(b'A'), INVERTED, INVERTED, INVERTED),    //GALAGA&GALAXIAN
+    title(0xa5, Some(b'R'), WHITE_BLUE, WHITE_BROWN, WHITE_BROWN), //BT2RAGNAROKWORLD
+    title(0xc6, Some(b' '), DARK_GREEN, WHITE_RED, WHITE_RED), //KEN GRIFFEY JR
+    title(0xd3, Some(b'I'), WHITE_OLIVE, WHITE_BROWN, WHITE_BROWN), //WARIOLAND2
+    title(0x27, Some(b'N'), WHITE_BLUE, WHITE_RED, WHITE_GREEN), //MAGNETIC SOCCER
+    title(0x61, Some(b'A'), WHITE_BLUE, WHITE_RED, WHITE_GREEN), //VEGAS STAKES
+    title(0x18, Some(b'I'), DARK_GREEN, WHITE_RED, WHITE_RED), //WARIO BLAST
+    title(0x66, Some(b'L'), DARK_GREEN, WHITE_RED, WHITE_RED), //MILLI/CENTI/PEDE
+    title(0x6a, Some(b'I'), GREEN, WHITE_BROWN, WHITE_BROWN), //MARIO & YOSHI
+    title(0xbf, Some(b'C'), FIELD, WHITE_SKY, WHITE_BROWN),   //SOCCER
+    title(0x0d, Some(b'E'), DARK_BLUE, GOLD, GOLD),           //POKEBOM
+    title(0xf4, Some(b' '), LIME_BROWN, WHITE_BROWN, WHITE_BROWN), //G&W GALLERY
+    title(0xb3, Some(b'R'), GREEN, GREEN, TRICOLOR),          //TETRIS ATTACK
+];
+
+//Sum of the 16 title bytes
+pub fn title_checksum(title: &[u8]) -> u8 {
+    title.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+//Picks the palette the CGB boot rom would use.  `header` is the cartridge starting at address 0.
+//A button combination held at boot overrides the title lookup.
+pub fn select_palette(header: &[u8], held_combo: Option<ManualPalette>) -> CompatPalette {
+    if let Some(combo) = held_combo {
+        return combo.palette();
+    }
+
+    if header.len() <= OLD_LICENSEE_ADDR || !is_nintendo_title(header) {
+        return DEFAULT_PALETTE.palette();
+    }
+
+    let title = &header[TITLE_START..=TITLE_END];
+    let checksum = title_checksum(title);
+
+    for entry in TITLE_TABLE.iter() {
+        let letter_matches = match entry.fourth_letter {
+            Some(letter) => title[DISAMBIGUATION_INDEX] == letter,
+            None => true,
+        };
+
+        if entry.checksum == checksum && letter_matches {
+            return entry.palette;
+        }
+    }
+
+    DEFAULT_PALETTE.palette()
+}
+
+//The boot rom only looks up titles published by Nintendo
+fn is_nintendo_title(header: &[u8]) -> bool {
+    match header[OLD_LICENSEE_ADDR] {
+        NINTENDO_OLD_LICENSEE => true,
+        USE_NEW_LICENSEE => header[NEW_LICENSEE_ADDR..NEW_LICENSEE_ADDR + 2] == NINTENDO_NEW_LICENSEE,
+        _ => false,
+    }
+}
+
+//Converts an RGB888 color into the CGB's RGB555 palette format
+pub fn rgb888_to_rgb555(color: u32) -> u16 {
+    let r = ((color >> 16) & 0xff) as u16 >> 3;
+    let g = ((color >> 8) & 0xff) as u16 >> 3;
+    let b = (color & 0xff) as u16 >> 3;
+    r | (g << 5) | (b << 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(title: &str, old_licensee: u8) -> Vec<u8> {
+        let mut header = vec![0; 0x150];
+        header[TITLE_START..TITLE_START + title.len()].copy_from_slice(title.as_bytes());
+        header[OLD_LICENSEE_ADDR] = old_licensee;
+        header
+    }
+
+    #[test]
+    fn title_checksum_sums_the_title_bytes() {
+        assert_eq!(title_checksum(b"POKEMON RED"), 0x14);
+        assert_eq!(title_checksum(b"TETRIS"), 0xdb);
+        assert_eq!(title_checksum(b"METROID2"), 0x46);
+        assert_eq!(title_checksum(b"SUPER MARIOLAND"), 0x46);
+    }
+
+    #[test]
+    fn shared_checksums_are_told_apart_by_the_fourth_letter() {
+        let metroid = select_palette(&header("METROID2", NINTENDO_OLD_LICENSEE), None);
+        assert_eq!(metroid.obj0, FIRE);
+
+        let mario = select_palette(&header("SUPER MARIOLAND", NINTENDO_OLD_LICENSEE), None);
+        assert_eq!(mario.bg, SKY);
+    }
+
+    //The japanese releases of Donkey Kong Land and the Game Boy Camera have their own checksums
+    #[test]
+    fn japanese_titles_have_their_own_entries() {
+        assert_eq!(title_checksum(b"SUPER DONKEYKONG"), 0xa8);
+        let donkey_kong = select_palette(&header("SUPER DONKEYKONG", NINTENDO_OLD_LICENSEE), None);
+        assert_eq!(donkey_kong.bg, DARK_BLUE);
+        assert_eq!(donkey_kong.obj1, GOLD);
+
+        assert_eq!(title_checksum(b"POCKETCAMERA"), 0x6f);
+        let camera = select_palette(&header("POCKETCAMERA", NINTENDO_OLD_LICENSEE), None);
+        assert_eq!(camera.bg, WHITE_RED);
+        assert_eq!(camera.obj0, WHITE_GREEN);
+    }
+
+    #[test]
+    fn only_nintendo_titles_are_looked_up() {
+        let tetris = select_palette(&header("TETRIS", NINTENDO_OLD_LICENSEE), None);
+        assert_eq!(tetris.bg, ORANGE);
+
+        let other = select_palette(&header("TETRIS", 0x08), None);
+        assert_eq!(other.bg, DEFAULT_PALETTE.palette().bg);
+
+        let mut new_licensee = header("TETRIS", USE_NEW_LICENSEE);
+        new_licensee[NEW_LICENSEE_ADDR..NEW_LICENSEE_ADDR + 2].copy_from_slice(&NINTENDO_NEW_LICENSEE);
+        assert_eq!(select_palette(&new_licensee, None).bg, ORANGE);
+    }
+
+    #[test]
+    fn held_buttons_make_a_combo() {
+        let mut buttons = Buttons::default();
+        assert!(ManualPalette::from_buttons(buttons).is_none());
+        buttons.set(Button::A, true);
+        assert!(ManualPalette::from_buttons(buttons).is_none());
+
+        buttons.set(Button::Left, true);
+        assert!(ManualPalette::from_buttons(buttons) == Some(ManualPalette::LeftA));
+        buttons.set(Button::A, false);
+        buttons.set(Button::B, true);
+        assert!(ManualPalette::from_buttons(buttons) == Some(ManualPalette::LeftB));
+        buttons.set(Button::B, false);
+        assert!(ManualPalette::from_buttons(buttons) == Some(ManualPalette::Left));
+    }
+
+    #[test]
+    fn a_held_combo_overrides_the_title() {
+        let palette = select_palette(&header("TETRIS", NINTENDO_OLD_LICENSEE), Some(ManualPalette::LeftB));
+        assert_eq!(palette.bg, GREYSCALE);
+    }
+}
@@ -1,7 +1,17 @@
+use crate::apu::*;
+use crate::audio_sink::AudioSink;
+use crate::color_profile::*;
+use crate::compat_palettes::*;
+use crate::debug_view::SpriteInfo;
+use crate::frame::*;
+use crate::gbs::{self, GbsFile};
+use crate::image::Image;
+use crate::joypad::*;
 use crate::memory_bank_controller::*;
 use crate::ppu::*;
 use crate::rom::*;
 use crate::timer::*;
+use crate::vgm::VgmWriter;
 use crate::vram::*;
 use crate::windows_interface::*;
 use crate::{instructions::Opcode, opcode_table::*};
@@ -17,9 +27,22 @@ pub struct Cpu {
     //Memory
     mcb: Mcb,
     lcd: Lcd,
+    apu: Apu,
     memory: [u8; 0x10000],
     ime: bool,
     halt: bool,
+    model: HardwareModel,
+    cycle_count: u64,                  //T-cycles since power on
+    boot_combo: Option<ManualPalette>, //Button combination held while the CGB boot rom runs
+    branch_cycles: u8,                 //Extra M-cycles of a conditional instruction that took its branch
+    stopped: bool,                     //Set by STOP until a button is pressed
+    double_speed: bool,                //CGB double speed, only entered by GBS songs that ask for it
+    joypad: Joypad,
+    input_source: Option<Box<dyn InputSource>>,
+    input_cycles: u32,                 //T-cycles since the input source was last polled
+    input_polled: bool,                //Set each time the input source is polled, for the frontend's hotkeys
+    vgm_error: Option<std::io::Error>, //Logging stops after an error
+    vgm_log: Option<VgmWriter>,
     /*
     rom_bank_0: [u8; self.BANK_00_END - self.BANK_00_START], //16KB ROM Bank 00     (in cartridge, fixed at bank 00)
     rom_bank_1: [u8; self.BANK_01_END - self.BANK_01_START], //16KB ROM Bank 01..NN (in cartridge, switchable bank number)
@@ -34,6 +57,12 @@ pub struct Cpu {
     */
 }
 
+#[derive(Copy, Clone, PartialEq)]
+pub enum HardwareModel {
+    Dmg,
+    Cgb,
+}
+
 #[derive(Copy, Clone)]
 pub enum Reg8bit {
     B = 0,
@@ -64,15 +93,29 @@ impl Cpu {
             timer: Timer::new(),
             mcb: Mcb::new(),
             lcd: Lcd::new(),
+            apu: Apu::new(),
             memory: [0; 0x10000],
             ime: false,
             halt: false,
+            model: HardwareModel::Dmg,
+            cycle_count: 0,
+            boot_combo: None,
+            branch_cycles: 0,
+            stopped: false,
+            double_speed: false,
+            joypad: Joypad::new(),
+            input_source: None,
+            input_cycles: 0,
+            input_polled: false,
+            vgm_log: None,
+            vgm_error: None,
         };
 
-        cpu.write_memory(LCD_Y_REG, 0x90);
-        cpu.write_memory(0xff00, 0x0f);
+        cpu.write_memory(LCD_CRTL_REG, 0x91);
+        cpu.write_memory(LCD_BGP_REG, 0xfc);
         cpu.write_memory(INTERRUPT_ENABLE_REG, 0x00);
         cpu.write_memory(INTERRUPT_FLAG_REG, 0xe0);
+        cpu.set_model(HardwareModel::Dmg);
 
         cpu
     }
@@ -83,16 +126,27 @@ impl Cpu {
         prifxed_instruct: &OpcodeTable,
         windows: &mut WindowsInterface,
     ) {
+        //STOP idles the cpu until a button is pressed
+        if self.stopped {
+            self.clock_hardware(STOPPED_M_CYCLES);
+            return;
+        }
+
+        //The cpu can be halted by instruction HALT (0x76), the cpu resumes when any enabled interrupt is pending.
+        //This is checked before the interrupt is dispatched, which clears its flag.
+        if self.halt && self.read_memory(INTERRUPT_ENABLE_REG) & self.read_memory(INTERRUPT_FLAG_REG) & 0x1f > 0 {
+            self.halt = false;
+        }
+
         //Check for and executes pending interrupts
-        self.check_interrupts();
+        let mut m_cycles = self.check_interrupts();
 
         let mut current_opcode = self.read_memory(self.pc as usize) as usize;
         let instruction: &Opcode;
 
         //windows.print_log_file(self);
 
-        //The cpu can be halted by instruction HALT (0x76), the cpu resumes if a timer interrupt is pending
-        if self.halt == false || self.get_interrupt_flag(TIMER) {
+        if !self.halt {
             //incrment pc by the length of the instruction.  This will cause pc to be ahead of the instuction currently being executed.
             self.pc = self
                 .pc
@@ -107,14 +161,239 @@ impl Cpu {
 
             //execute the instruction
             (instruction.handler)(&instruction, self);
+            m_cycles += instruction.number_of_cycles + self.branch_cycles;
+            self.branch_cycles = 0;
         } else {
-            //this is the instriction if the cpu is halted
-            instruction = &unprifxed_instruct.table[current_opcode];
+            //A halted cpu still clocks the rest of the hardware, one M-cycle at a time
+            m_cycles += HALTED_M_CYCLES;
         }
 
-        if self.timer.update_timers(instruction.number_of_cycles) {
-            self.set_interrupt_pending(TIMER);
+        self.clock_hardware(m_cycles);
+    }
+
+    //Returns true once per vblank, when a new frame can be read with frame()
+    pub fn frame_ready(&mut self) -> bool {
+        self.lcd.frame_ready()
+    }
+
+    //Returns true once each time the buttons are read, about once a frame even while the lcd is off
+    pub fn input_polled(&mut self) -> bool {
+        std::mem::take(&mut self.input_polled)
+    }
+
+    //Number of T-cycles run since power on.  Used to keep recordings in sync.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycle_count
+    }
+
+    //Host sample rate of the audio output
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.apu.set_sample_rate(sample_rate);
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.apu.sample_rate()
+    }
+
+    //Stereo audio produced since the last call, left and right interleaved
+    pub fn take_audio_samples(&mut self) -> Vec<i16> {
+        self.apu.take_samples_i16()
+    }
+
+    //Sends the audio to a sink as it's produced, e.g. a WavSink or the RingBufferSink of a real-time frontend
+    pub fn set_audio_sink(&mut self, sink: Box<dyn AudioSink>) {
+        self.apu.set_audio_sink(sink);
+    }
+
+    pub fn finish_audio_sink(&mut self) -> std::io::Result<()> {
+        self.apu.finish_audio_sink()
+    }
+
+    //Logs every write to the sound registers to a VGM file, starting with writes that restore the current settings
+    pub fn start_vgm_log(&mut self, path: &str) -> std::io::Result<()> {
+        let mut log = VgmWriter::create(path, self.cycle_count)?;
+        for (index, data) in self.apu.register_writes() {
+            log.write_register(index, data, self.cycle_count)?;
         }
+
+        self.vgm_log = Some(log);
+        self.vgm_error = None;
+        Ok(())
+    }
+
+    //Ends the VGM log.  Returns the first error logging had.
+    pub fn stop_vgm_log(&mut self) -> std::io::Result<()> {
+        let log = self.vgm_log.take();
+        if let Some(error) = self.vgm_error.take() {
+            return Err(error);
+        }
+
+        match log {
+            Some(log) => log.finish(self.cycle_count),
+            None => Ok(()),
+        }
+    }
+
+    //Mutes a channel (1-4) in the mix without changing the apu's state
+    pub fn set_channel_muted(&mut self, channel: usize, muted: bool) {
+        self.apu.set_channel_muted(channel, muted);
+    }
+
+    pub fn channel_muted(&self, channel: usize) -> bool {
+        self.apu.channel_muted(channel)
+    }
+
+    pub fn solo_channel(&mut self, channel: Option<usize>) {
+        self.apu.solo_channel(channel);
+    }
+
+    //One sink per channel, each getting that channel on its own
+    pub fn set_channel_sinks(&mut self, sinks: Vec<Box<dyn AudioSink>>) {
+        self.apu.set_channel_sinks(sinks);
+    }
+
+    pub fn finish_channel_sinks(&mut self) -> std::io::Result<()> {
+        self.apu.finish_channel_sinks()
+    }
+
+    //Borrows the last completed frame
+    pub fn frame(&self) -> Frame<'_> {
+        self.lcd.frame()
+    }
+
+    //Image of the last completed frame with the current palette and color correction, scaled by a whole number
+    pub fn screenshot(&self, scale: usize) -> Image {
+        self.lcd.frame().to_image(scale)
+    }
+
+    pub fn save_screenshot(&self, path: &str, scale: usize) -> std::io::Result<()> {
+        self.screenshot(scale).save_png(path)
+    }
+
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.lcd.set_dmg_palette(palette);
+    }
+
+    //Debug image of every tile in vram, drawn with the colors of a DMG palette
+    pub fn tile_sheet(&self, palette: DmgPalette) -> Image {
+        self.lcd.tile_sheet(palette.colors())
+    }
+
+    //Debug image of a background map (0x9800 or 0x9c00, None for the one the background uses) with the
+    //scroll viewport and the window outlined
+    pub fn tile_map(&self, map_address: Option<usize>, palette: DmgPalette) -> Image {
+        self.lcd.tile_map(map_address, palette.colors())
+    }
+
+    //Decoded OAM for the debugger.  Call it once per frame to follow the sprites.
+    pub fn oam_info(&self) -> Vec<SpriteInfo> {
+        self.lcd.oam_info()
+    }
+
+    //Debug image of the 40 sprites drawn on their own, in OAM order
+    pub fn sprite_sheet(&self, palette: DmgPalette) -> Image {
+        self.lcd.sprite_sheet(palette.colors())
+    }
+
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.lcd.set_color_correction(correction);
+    }
+
+    //Selects the ppu implementation used to draw mode 3
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.lcd.set_renderer(renderer);
+    }
+
+    pub fn set_model(&mut self, model: HardwareModel) {
+        self.model = model;
+        self.apu.set_dmg_hardware(model == HardwareModel::Dmg);
+    }
+
+    //Loads a GBS and starts one of its songs (0 based).  The play routine is then called by the VBlank or timer
+    //interrupt, like in the game the music came from.
+    pub fn start_gbs_song(&mut self, gbs: &GbsFile, song: u8) {
+        self.mcb.load_image(&gbs.rom_image());
+
+        for address in (EXTERNAL_RAM_START..=WRAM_BANK_1_END).chain(HRAM_START..=HRAM_END) {
+            self.memory[address] = 0;
+        }
+
+        //Power cycle the apu so the song starts from cleared registers
+        self.write_memory(NR52_REG, 0x00);
+        self.write_memory(NR52_REG, 0x80);
+        self.write_memory(NR50_REG, 0x77);
+        self.write_memory(NR51_REG, 0xff);
+
+        self.write_memory(TMA_REG, gbs.timer_modulo);
+        self.write_memory(TAC_REG, gbs.timer_control & !gbs::TAC_DOUBLE_SPEED);
+        self.double_speed = gbs.double_speed();
+        self.write_memory(INTERRUPT_FLAG_REG, 0x00);
+        let play_interrupt = if gbs.uses_timer() { TIMER } else { V_BLANK };
+        self.write_memory(INTERRUPT_ENABLE_REG, 1 << play_interrupt);
+
+        //Init runs with the song number in A and returns to the idle loop, which enables the interrupts
+        self.registers = [0; 8];
+        self.registers[Reg8bit::A as usize] = song;
+        self.ime = false;
+        self.halt = false;
+        self.sp = gbs.stack_pointer;
+        self.pc = gbs::IDLE_LOOP_ADDR;
+        self.call(gbs.init_address);
+    }
+
+    //Sets the buttons the frontend polls each frame
+    pub fn set_input_source(&mut self, source: Box<dyn InputSource>) {
+        self.input_source = Some(source);
+    }
+
+    //Updates the buttons held down.  Pressing a selected button requests the joypad interrupt and ends STOP.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        if self.joypad.set_buttons(buttons) {
+            self.set_interrupt_pending(JOYPAD);
+        }
+        if self.joypad.any_selected_pressed() {
+            self.stopped = false;
+        }
+    }
+
+    //Selects one of the manual compatibility palettes, like holding the buttons during the CGB boot animation
+    pub fn set_boot_combo(&mut self, combo: Option<ManualPalette>) {
+        self.boot_combo = combo;
+    }
+
+    //Sets up the hardware for the loaded cartridge the way the boot rom leaves it.
+    //On a CGB, games that support it run in CGB mode and DMG games are colorized with the compatibility palettes.
+    pub fn init_cartridge(&mut self) {
+        if self.model == HardwareModel::Dmg {
+            self.lcd.set_cgb_mode(false);
+            return;
+        }
+
+        //A = 0x11 after the boot rom tells the game it is running on a CGB
+        self.registers[Reg8bit::A as usize] = 0x11;
+
+        if self.read_memory(CGB_FLAG_ADDR) & CGB_FLAG_MASK > 0 {
+            self.lcd.set_cgb_mode(true);
+        } else {
+            let mut header = [0u8; OLD_LICENSEE_ADDR + 1];
+            for (i, byte) in header.iter_mut().enumerate() {
+                *byte = self.read_memory(i);
+            }
+            //--palette wins over the buttons the input source holds at boot
+            let held_combo = self.input_source.as_mut().map(|source| source.boot_buttons());
+            let combo = self.boot_combo.or(held_combo.and_then(ManualPalette::from_buttons));
+            self.lcd.load_compat_palette(&select_palette(&header, combo));
+        }
+    }
+
+    //Enables/disables blocking cpu access to vram and oam while the ppu is using them
+    pub fn set_vram_access_blocking(&mut self, enabled: bool) {
+        self.lcd.set_access_blocking(enabled);
+    }
+
+    //Returns the number of vram/oam writes the ppu dropped since the last call
+    pub fn take_blocked_vram_writes(&mut self) -> u32 {
+        self.lcd.take_blocked_writes()
     }
 
     //Write 8 bit register with value n
@@ -200,10 +479,26 @@ impl Cpu {
     pub fn write_memory(&mut self, index: usize, n: u8) {
         match index {
             //Writes to this section of read only memory are used to update control registers of the memory bank controller
-            ROM_BANK_00_START..=ROM_BANK_01_END => self.mcb.change_bank(),
+            ROM_BANK_00_START..=ROM_BANK_01_END => self.mcb.change_bank(index, n),
+            JOYPAD_REG => {
+                if self.joypad.write_register(n) {
+                    self.set_interrupt_pending(JOYPAD);
+                }
+            }
             TIMER_ADDR_START..=TIMER_ADDR_END => self.timer.write_memory(index, n),
+            APU_ADDR_START..=APU_ADDR_END => {
+                self.log_sound_write(index, n);
+                self.apu.write_register(index, n);
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => {
+                self.log_sound_write(index, n);
+                self.apu.write_wave_ram(index, n);
+            }
             VRAM_START..=VRAM_END => self.lcd.write_vram(index, n),
+            OAM_START..=OAM_END => self.lcd.write_oam(index, n),
+            LCD_DMA_REG => self.dma_transfer(n),
             LCD_ADDR_START..=LCD_ADDR_END => self.lcd.write_register(index, n),
+            VRAM_BANK_REG | BCPS_REG..=OCPD_REG => self.lcd.write_cgb_register(index, n),
             _ => self.memory[index] = n,
         }
     }
@@ -214,9 +509,14 @@ impl Cpu {
         match index {
             ROM_BANK_00_START..=ROM_BANK_00_END => self.mcb.read_bank_00(index),
             ROM_BANK_01_START..=ROM_BANK_01_END => self.mcb.read_bank_n(index),
+            JOYPAD_REG => self.joypad.read_register(),
             TIMER_ADDR_START..=TIMER_ADDR_END => self.timer.read_memory(index),
+            APU_ADDR_START..=APU_ADDR_END => self.apu.read_register(index),
+            WAVE_RAM_START..=WAVE_RAM_END => self.apu.read_wave_ram(index),
             VRAM_START..=VRAM_END => self.lcd.read_vram(index),
+            OAM_START..=OAM_END => self.lcd.read_oam(index),
             LCD_ADDR_START..=LCD_ADDR_END => self.lcd.read_register(index),
+            VRAM_BANK_REG | BCPS_REG..=OCPD_REG => self.lcd.read_cgb_register(index),
             _ => self.memory[index],
         }
     }
@@ -248,6 +548,11 @@ impl Cpu {
         (self.registers[Reg8bit::F as usize] & F_ADD_SUB_SET) > 0
     }
 
+    //Adds the M-cycles a conditional jump, call or return takes when the condition is met
+    pub fn add_branch_cycles(&mut self, m_cycles: u8) {
+        self.branch_cycles += m_cycles;
+    }
+
     pub fn enable_interupts(&mut self) {
         self.ime = true;
     }
@@ -846,7 +1151,14 @@ impl Cpu {
         self.halt = true;
     }
 
-    pub fn check_interrupts(&mut self) {
+    //Enters STOP, which also resets DIV.  A button already held means there's nothing to wait for.
+    pub fn set_stop(&mut self) {
+        self.write_memory(DIV_REG, 0);
+        self.stopped = !self.joypad.any_selected_pressed();
+    }
+
+    //Returns the M-cycles spent dispatching an interrupt
+    pub fn check_interrupts(&mut self) -> u8 {
         let enable_flag = self.read_memory(INTERRUPT_ENABLE_REG);
         let mut interrupt_flag = self.read_memory(INTERRUPT_FLAG_REG);
 
@@ -876,7 +1188,14 @@ impl Cpu {
 
             //clear the flag
             self.write_memory(INTERRUPT_FLAG_REG, interrupt_flag);
+
+            //ime is cleared when an interrupt was called
+            if !self.ime {
+                return INTERRUPT_DISPATCH_M_CYCLES;
+            }
         }
+
+        0
     }
 
     //Set the interrupt pending
@@ -886,10 +1205,68 @@ impl Cpu {
         self.write_memory(INTERRUPT_FLAG_REG, interrupt_flag);
     }
 
-    #[inline]
-    fn get_interrupt_flag(&mut self, interrupt: u8) -> bool {
-        let interrupt_flag = self.read_memory(INTERRUPT_FLAG_REG);
-        interrupt_flag & (1 << interrupt) > 0
+    //Runs the hardware alongside the cpu for a number of M-cycles
+    fn clock_hardware(&mut self, m_cycles: u8) {
+        //The opcode table stores M-cycles, the hardware is clocked by T-cycles
+        let cpu_cycles = m_cycles as u32 * T_CYCLES_PER_M_CYCLE;
+
+        //The timer is held while the cpu is stopped
+        if !self.stopped && self.timer.update_timers(cpu_cycles) {
+            self.set_interrupt_pending(TIMER);
+        }
+
+        //In double speed the cpu and the timer run twice as fast as the ppu and the apu
+        let cycles = if self.double_speed { cpu_cycles / 2 } else { cpu_cycles };
+        self.cycle_count += cycles as u64;
+        self.input_cycles += cycles;
+        let lcd_requests = self.lcd.update_lcd(cycles);
+        if lcd_requests & V_BLANK_REQUEST > 0 {
+            self.set_interrupt_pending(V_BLANK);
+        }
+        //There are no vblanks while the lcd is off, which games usually do before STOP, so the buttons are also
+        //read after a frame's worth of cycles
+        if lcd_requests & V_BLANK_REQUEST > 0 || self.input_cycles >= DOTS_PER_FRAME {
+            self.poll_input();
+        }
+        if lcd_requests & LCD_STAT_REQUEST > 0 {
+            self.set_interrupt_pending(LCD_STAT);
+        }
+
+        //The apu's frame sequencer is clocked by DIV so it runs after the timer.  In double speed it uses the next bit
+        //of DIV so it keeps its rate.
+        let div = self.timer.read_memory(DIV_REG);
+        self.apu
+            .update_apu(cycles, if self.double_speed { div >> 1 } else { div });
+    }
+
+    //Reads the buttons from the input source once per frame
+    fn poll_input(&mut self) {
+        self.input_cycles = 0;
+        self.input_polled = true;
+        if let Some(source) = self.input_source.as_mut() {
+            let buttons = source.poll();
+            self.set_buttons(buttons);
+        }
+    }
+
+    //Adds a write to the sound registers to the VGM log
+    fn log_sound_write(&mut self, index: usize, n: u8) {
+        if let Some(log) = self.vgm_log.as_mut() {
+            if let Err(error) = log.write_register(index, n, self.cycle_count) {
+                self.vgm_error = Some(error);
+                self.vgm_log = None;
+            }
+        }
+    }
+
+    //Copies 160 bytes from 0xXX00 into OAM.  The transfer is done instantly instead of over 160 M-cycles.
+    fn dma_transfer(&mut self, source: u8) {
+        self.lcd.write_register(LCD_DMA_REG, source);
+        let start = (source as usize) << 8;
+        for i in 0..OAM_SIZE {
+            let data = self.read_memory(start + i);
+            self.lcd.dma_write_oam(OAM_START + i, data);
+        }
     }
 }
 
@@ -1069,17 +1446,23 @@ const F_ADD_SUB_CLR: u8 = 0xbf; //1011 1111
 const F_HALF_CARRY_CLR: u8 = 0xdf; //1101 1111
 const F_CARRY_CLR: u8 = 0xef; //1110 1111
 
+pub const CPU_CLOCK_HZ: u64 = 4_194_304; //T-cycles per second
+const T_CYCLES_PER_M_CYCLE: u32 = 4;
+const INTERRUPT_DISPATCH_M_CYCLES: u8 = 5;
+const HALTED_M_CYCLES: u8 = 1;
+const STOPPED_M_CYCLES: u8 = 1;
+
 const V_BLANK: u8 = 0;
 const LCD_STAT: u8 = 1;
 const TIMER: u8 = 2;
 const SERIAL: u8 = 3;
 const JOYPAD: u8 = 4;
 
-const V_BLANK_ADDR: u16 = 0x40;
-const LCD_STAT_ADDR: u16 = 0x48;
-const TIMER_ADDR: u16 = 0x50;
-const SERIAL_ADDR: u16 = 0x58;
-const JOYPAD_ADDR: u16 = 0x60;
+pub const V_BLANK_ADDR: u16 = 0x40;
+pub const LCD_STAT_ADDR: u16 = 0x48;
+pub const TIMER_ADDR: u16 = 0x50;
+pub const SERIAL_ADDR: u16 = 0x58;
+pub const JOYPAD_ADDR: u16 = 0x60;
 
 const EXTERNAL_RAM_START: usize = 0xa000;
 const EXTERNAL_RAM_END: usize = 0xbfff;
@@ -1089,8 +1472,6 @@ const WRAM_BANK_1_START: usize = 0xd000;
 const WRAM_BANK_1_END: usize = 0xdfff;
 const ECHO_START: usize = 0xe000;
 const ECHO_END: usize = 0xfdff;
-const OAM_START: usize = 0xfe00;
-const OAM_END: usize = 0xfe9f;
 const NOT_USABLE_START: usize = 0xfea0;
 const NOT_USABLE_END: usize = 0xfeff;
 const IO_START: usize = 0xff00;
@@ -1145,3 +1526,137 @@ impl FlagRegister {
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::ScriptedInput;
+
+    const HALT: u8 = 0x76;
+    const NOP: u8 = 0x00;
+    const STOP: u8 = 0x10;
+
+    fn step(cpu: &mut Cpu, count: usize) {
+        let unprefixed = OpcodeTable::init_unprefix_instruction_table();
+        let prefixed = OpcodeTable::init_prefix_instruction_table();
+        let mut windows = WindowsInterface::new();
+        for _ in 0..count {
+            cpu.execute_step(&unprefixed, &prefixed, &mut windows);
+        }
+    }
+
+    #[test]
+    fn halt_runs_the_handler_of_the_interrupt_that_woke_it() {
+        let mut cpu = Cpu::new();
+        cpu.load_read_only_data(0x0100, HALT);
+        cpu.load_read_only_data(0x0101, NOP);
+        cpu.load_read_only_data(TIMER_ADDR as usize, NOP);
+        cpu.write_memory(INTERRUPT_ENABLE_REG, 1 << TIMER);
+        cpu.write_memory(INTERRUPT_FLAG_REG, 0);
+        cpu.enable_interupts();
+
+        step(&mut cpu, 3);
+        assert!(cpu.halt);
+        assert_eq!(cpu.read_pc(), 0x0101);
+
+        //The interrupt is dispatched and the first instruction of the handler runs in the same step
+        cpu.set_interrupt_pending(TIMER);
+        step(&mut cpu, 1);
+        assert!(!cpu.halt);
+        assert_eq!(cpu.read_pc(), TIMER_ADDR + 1);
+        assert_eq!(cpu.read_memory_nn(cpu.read_sp() as usize), 0x0101);
+    }
+
+    #[test]
+    fn stop_ends_when_a_selected_button_is_pressed() {
+        let mut cpu = Cpu::new();
+        cpu.load_read_only_data(0x0100, STOP);
+        cpu.load_read_only_data(0x0101, 0x00);
+        cpu.load_read_only_data(0x0102, NOP);
+        cpu.write_memory(JOYPAD_REG, 0x20); //Select the d-pad
+        cpu.write_memory(INTERRUPT_FLAG_REG, 0);
+
+        step(&mut cpu, 3);
+        assert!(cpu.stopped);
+        assert_eq!(cpu.read_pc(), 0x0102);
+        assert_eq!(cpu.read_memory(DIV_REG), 0);
+
+        //A button on the line that isn't selected doesn't wake the cpu
+        let mut buttons = Buttons::default();
+        buttons.set(Button::Start, true);
+        cpu.set_buttons(buttons);
+        assert!(cpu.stopped);
+        assert_eq!(cpu.read_memory(INTERRUPT_FLAG_REG) & (1 << JOYPAD), 0);
+
+        buttons.set(Button::Down, true);
+        cpu.set_buttons(buttons);
+        assert!(!cpu.stopped);
+        assert!(cpu.read_memory(INTERRUPT_FLAG_REG) & (1 << JOYPAD) > 0);
+        step(&mut cpu, 1);
+        assert_eq!(cpu.read_pc(), 0x0103);
+    }
+
+    #[test]
+    fn stop_with_the_lcd_off_ends_when_the_input_source_presses_a_button() {
+        let mut cpu = Cpu::new();
+        cpu.load_read_only_data(0x0100, STOP);
+        cpu.load_read_only_data(0x0101, 0x00);
+        cpu.load_read_only_data(0x0102, NOP);
+        cpu.write_memory(LCD_CRTL_REG, 0);
+        cpu.write_memory(JOYPAD_REG, 0x20); //Select the d-pad
+        cpu.set_input_source(Box::new(ScriptedInput::parse("3 down").unwrap()));
+
+        //Still stopped after two frames
+        let frame_steps = (DOTS_PER_FRAME / T_CYCLES_PER_M_CYCLE) as usize;
+        step(&mut cpu, 2 + frame_steps * 2);
+        assert!(cpu.stopped);
+        assert_eq!(cpu.read_pc(), 0x0102);
+
+        //The third poll presses Down and the cpu carries on with the NOPs after STOP
+        step(&mut cpu, frame_steps);
+        assert!(!cpu.stopped);
+        assert!(cpu.read_pc() > 0x0102);
+    }
+
+    //A GBS whose play routine counts its calls at 0xc000
+    fn counting_gbs(timer_control: u8, timer_modulo: u8) -> GbsFile {
+        let mut bytes = vec![0; 0x70];
+        bytes[..4].copy_from_slice(&[b'G', b'B', b'S', 1]);
+        bytes[0x04] = 1; //Songs
+        bytes[0x05] = 1; //First song
+        bytes[0x06..0x0e].copy_from_slice(&[0x00, 0x04, 0x00, 0x04, 0x01, 0x04, 0xfe, 0xff]);
+        bytes[0x0e] = timer_modulo;
+        bytes[0x0f] = timer_control;
+        //init: ret.  play: ld hl, 0xc000; inc (hl); ret
+        bytes.extend_from_slice(&[0xc9, 0x21, 0x00, 0xc0, 0x34, 0xc9]);
+
+        GbsFile::parse(&bytes).unwrap()
+    }
+
+    fn play_calls(gbs: &GbsFile, cycles: u64) -> u8 {
+        let unprefixed = OpcodeTable::init_unprefix_instruction_table();
+        let prefixed = OpcodeTable::init_prefix_instruction_table();
+        let mut windows = WindowsInterface::new();
+
+        let mut cpu = Cpu::new();
+        cpu.start_gbs_song(gbs, 0);
+        while cpu.cycle_count() < cycles {
+            cpu.execute_step(&unprefixed, &prefixed, &mut windows);
+        }
+        cpu.read_memory(0xc000)
+    }
+
+    #[test]
+    fn double_speed_gbs_songs_play_twice_as_often() {
+        //A 4096 Hz timer reloaded with 0xc0 overflows 64 times a second.  TIMA starts at 0 so the first call comes
+        //after 256 ticks, 1/16 of a second, and 15 more follow by 0.3 seconds.
+        let normal = counting_gbs(0x04, 0xc0);
+        assert_eq!(normal.play_rate(), 64.0);
+        assert_eq!(play_calls(&normal, CPU_CLOCK_HZ * 3 / 10), 16);
+
+        //Twice the rate, the first call comes after 1/32 of a second and 34 more follow
+        let double = counting_gbs(0x84, 0xc0);
+        assert_eq!(double.play_rate(), 128.0);
+        assert_eq!(play_calls(&double, CPU_CLOCK_HZ * 3 / 10), 35);
+    }
+}
@@ -0,0 +1,220 @@
+use std::vec::Vec;
+
+use crate::image::*;
+use crate::ppu::*;
+use crate::vram::*;
+
+//Debug images of the ppu's memory.  These read vram directly so they show what the game wrote even when it is
+//not on screen.
+
+const SHEET_COLUMNS: usize = 16; //Tiles per row of the tile sheet
+const SHEET_ROWS: usize = BLOCKS_PER_VRAM * TILES_PER_BANK / SHEET_COLUMNS;
+const TILE_SIZE: usize = 8;
+
+//Draws the 384 tiles of each vram bank as a 16x24 grid.  Tile 0x8000 is the top left tile and the tiles follow in
+//address order.  Each bank is placed to the right of the previous one.
+//`colors` are the RGB888 colors of the 4 color indexes.
+pub fn tile_sheet(banks: &[Vram], colors: [u32; 4]) -> Image {
+    let bank_width = SHEET_COLUMNS * TILE_SIZE;
+    let mut image = Image::new(bank_width * banks.len(), SHEET_ROWS * TILE_SIZE, colors[0]);
+
+    for (bank_index, bank) in banks.iter().enumerate() {
+        for tile_number in 0..BLOCKS_PER_VRAM * TILES_PER_BANK {
+            let block_index = tile_number / TILES_PER_BANK;
+            let tile_index = tile_number % TILES_PER_BANK;
+            let tile_x = bank_index * bank_width + (tile_number % SHEET_COLUMNS) * TILE_SIZE;
+            let tile_y = (tile_number / SHEET_COLUMNS) * TILE_SIZE;
+
+            for line_index in 0..LINES_PER_TILE {
+                if let Some(line) = bank.cache_tile_line(block_index, tile_index, line_index) {
+                    for (column, color) in line.iter().enumerate() {
+                        image.set_pixel(tile_x + column, tile_y + line_index, colors[*color as usize]);
+                    }
+                }
+            }
+        }
+    }
+
+    //return
+    image
+}
+
+const MAP_SIZE: usize = 256; //32x32 tiles
+const VIEWPORT_COLOR: u32 = 0xff0000;
+const WINDOW_COLOR: u32 = 0x0000ff;
+
+//Draws the whole 256x256 background map at map_address (0x9800 or 0x9c00) using the current tile data addressing
+//and, in CGB mode, the map attributes.  The area of the map shown on screen is outlined in red and the part of the
+//screen covered by the window is outlined in blue.  Both outlines wrap around the edges of the map like the
+//scrolling does.
+pub fn tile_map(bus: &PpuBus, map_address: usize, colors: [u32; 4]) -> Image {
+    let mut image = Image::new(MAP_SIZE, MAP_SIZE, colors[0]);
+
+    for row in 0..(MAP_SIZE / TILE_SIZE) as u8 {
+        for column in 0..(MAP_SIZE / TILE_SIZE) as u8 {
+            let tile = bus.map_tile(map_address, column, row);
+            let attributes = bus.map_attributes(map_address, column, row);
+
+            for line_index in 0..LINES_PER_TILE as u8 {
+                let line = bus.bg_tile_line(tile, line_index, attributes);
+                for x in 0..TILE_SIZE {
+                    let pixel_column = if attributes & ATTR_X_FLIP > 0 { 7 - x } else { x };
+                    let color = colors[line[pixel_column] as usize];
+                    image.set_pixel(
+                        column as usize * TILE_SIZE + x,
+                        row as usize * TILE_SIZE + line_index as usize,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    let scx = bus.register(LCD_SCX_REG) as usize;
+    let scy = bus.register(LCD_SCY_REG) as usize;
+    draw_wrapped_rect(&mut image, scx, scy, LCD_WIDTH, LCD_HEIGHT, VIEWPORT_COLOR);
+
+    //The window starts at WX - 7, WY on screen and covers everything to the right and below
+    let wx = bus.register(LCD_WX_REG) as usize;
+    let wy = bus.register(LCD_WY_REG) as usize;
+    let window_enabled = bus.register(LCD_CRTL_REG) & LCDC_WINDOW_ENABLE > 0;
+    if window_enabled && wx < LCD_WIDTH + 7 && wy < LCD_HEIGHT {
+        let window_x = wx.saturating_sub(7);
+        draw_wrapped_rect(
+            &mut image,
+            scx + window_x,
+            scy + wy,
+            LCD_WIDTH - window_x,
+            LCD_HEIGHT - wy,
+            WINDOW_COLOR,
+        );
+    }
+
+    //return
+    image
+}
+
+//Outlines a rectangle on the map, wrapping at its edges
+fn draw_wrapped_rect(image: &mut Image, x: usize, y: usize, width: usize, height: usize, color: u32) {
+    for offset in 0..width {
+        image.set_pixel((x + offset) % MAP_SIZE, y % MAP_SIZE, color);
+        image.set_pixel((x + offset) % MAP_SIZE, (y + height - 1) % MAP_SIZE, color);
+    }
+    for offset in 0..height {
+        image.set_pixel(x % MAP_SIZE, (y + offset) % MAP_SIZE, color);
+        image.set_pixel((x + width - 1) % MAP_SIZE, (y + offset) % MAP_SIZE, color);
+    }
+}
+
+const SPRITE_COUNT: usize = OAM_SIZE / BYTES_PER_SPRITE;
+const SPRITE_SHEET_COLUMNS: usize = 8;
+const SPRITE_CELL_WIDTH: usize = TILE_SIZE + 1; //1 pixel gap between sprites
+const SPRITE_CELL_HEIGHT: usize = 2 * TILE_SIZE + 1; //Room for 8x16 sprites
+const TRANSPARENT_COLOR: u32 = 0xff00ff;
+
+//One OAM entry decoded for the debugger
+#[derive(Copy, Clone)]
+pub struct SpriteInfo {
+    pub index: u8, //Position in OAM, 0-39
+    pub x: u8,     //Screen X + 8, as stored in OAM
+    pub y: u8,     //Screen Y + 16, as stored in OAM
+    pub tile: u8,
+    pub flags: u8,
+    pub palette: u8,       //OBP0/OBP1 in DMG mode, palette 0-7 in CGB mode
+    pub lines_visible: u8, //Lines of the frame the OAM scan picked this sprite on
+    pub lines_dropped: u8, //Lines the sprite covers but lost to the 10 sprites per line limit
+}
+
+//Decodes all 40 OAM entries.  The OAM scan is replayed for every visible line with the current LCDC sprite size,
+//so sprites that flicker because of the per line limit have lines_dropped > 0.
+pub fn oam_info(bus: &PpuBus) -> Vec<SpriteInfo> {
+    let height = sprite_height(bus) as i16;
+    let mut sprites: Vec<SpriteInfo> = (0..SPRITE_COUNT)
+        .map(|index| {
+            let entry = &bus.oam[index * BYTES_PER_SPRITE..(index + 1) * BYTES_PER_SPRITE];
+            let flags = entry[3];
+            let palette = if bus.cgb_mode {
+                flags & ATTR_CGB_PALETTE
+            } else {
+                (flags & SPRITE_DMG_PALETTE > 0) as u8
+            };
+
+            SpriteInfo {
+                index: index as u8,
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                flags,
+                palette,
+                lines_visible: 0,
+                lines_dropped: 0,
+            }
+        })
+        .collect();
+
+    for line in 0..LCD_HEIGHT as i16 {
+        let mut selected = 0;
+        for sprite in sprites.iter_mut() {
+            let y = sprite.y as i16 - 16;
+            if line >= y && line < y + height {
+                if selected < MAX_SPRITES_PER_LINE {
+                    sprite.lines_visible += 1;
+                    selected += 1;
+                } else {
+                    sprite.lines_dropped += 1;
+                }
+            }
+        }
+    }
+
+    //return
+    sprites
+}
+
+//Draws every sprite on its own in OAM order, 8 sprites per row, ignoring its position and the background.
+//Flips and the CGB vram bank are applied.  Transparent pixels are magenta.
+pub fn sprite_sheet(bus: &PpuBus, colors: [u32; 4]) -> Image {
+    let rows = SPRITE_COUNT / SPRITE_SHEET_COLUMNS;
+    let mut image = Image::new(
+        SPRITE_SHEET_COLUMNS * SPRITE_CELL_WIDTH,
+        rows * SPRITE_CELL_HEIGHT,
+        TRANSPARENT_COLOR,
+    );
+    let height = sprite_height(bus);
+
+    for sprite in oam_info(bus) {
+        let cell_x = (sprite.index as usize % SPRITE_SHEET_COLUMNS) * SPRITE_CELL_WIDTH;
+        let cell_y = (sprite.index as usize / SPRITE_SHEET_COLUMNS) * SPRITE_CELL_HEIGHT;
+        let tile = if height == 16 { sprite.tile & 0xfe } else { sprite.tile };
+        let bank = (bus.cgb_mode && sprite.flags & ATTR_VRAM_BANK > 0) as usize;
+
+        for y in 0..height {
+            let line = if sprite.flags & SPRITE_Y_FLIP > 0 {
+                height - 1 - y
+            } else {
+                y
+            };
+            let address = VRAM_START + (tile as usize + line / TILE_SIZE) * BYTES_PER_TILE;
+            let pixels = bus.vram[bank].tile_line_at(address, (line % TILE_SIZE) as u8);
+
+            for x in 0..TILE_SIZE {
+                let column = if sprite.flags & SPRITE_X_FLIP > 0 { 7 - x } else { x };
+                let color = pixels[column];
+                if color != 0 {
+                    image.set_pixel(cell_x + x, cell_y + y, colors[color as usize]);
+                }
+            }
+        }
+    }
+
+    //return
+    image
+}
+
+fn sprite_height(bus: &PpuBus) -> usize {
+    if bus.register(LCD_CRTL_REG) & LCDC_OBJ_SIZE > 0 {
+        2 * TILE_SIZE
+    } else {
+        TILE_SIZE
+    }
+}
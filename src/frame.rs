@@ -0,0 +1,175 @@
+use crate::color_profile::*;
+use crate::image::Image;
+use crate::ppu::*;
+
+//Formats a completed frame can be read in
+#[derive(Copy, Clone, PartialEq)]
+pub enum PixelFormat {
+    Shades,   //1 byte per pixel.  The DMG shade 0-3, or the color index in CGB mode.
+    Rgba8888, //4 bytes per pixel: R, G, B, A
+    Rgb565,   //2 bytes per pixel, big endian as expected by SPI displays
+    Argb8888, //4 bytes per pixel: A, R, G, B
+}
+
+impl PixelFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "shades" => Some(PixelFormat::Shades),
+            "rgba8888" => Some(PixelFormat::Rgba8888),
+            "rgb565" => Some(PixelFormat::Rgb565),
+            "argb8888" => Some(PixelFormat::Argb8888),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Shades => 1,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Rgba8888 | PixelFormat::Argb8888 => 4,
+        }
+    }
+
+    //Size of a whole frame in this format
+    pub fn frame_size(&self) -> usize {
+        SCREEN_RESOLUTION * self.bytes_per_pixel()
+    }
+}
+
+//Borrow of the last completed frame.  Nothing is copied until a conversion is asked for.
+#[derive(Copy, Clone)]
+pub struct Frame<'a> {
+    shades: &'a [u8; SCREEN_RESOLUTION],
+    colors: &'a [u16; SCREEN_RESOLUTION],
+    dmg: bool, //The frame was drawn in DMG mode so the shades are mapped with the DMG palette
+    profile: &'a ColorProfile,
+}
+
+impl<'a> Frame<'a> {
+    pub fn new(
+        shades: &'a [u8; SCREEN_RESOLUTION],
+        colors: &'a [u16; SCREEN_RESOLUTION],
+        dmg: bool,
+        profile: &'a ColorProfile,
+    ) -> Self {
+        Self {
+            shades,
+            colors,
+            dmg,
+            profile,
+        }
+    }
+
+    pub fn shades(&self) -> &'a [u8; SCREEN_RESOLUTION] {
+        self.shades
+    }
+
+    //Returns one pixel as RGB888 after the DMG palette or the color correction is applied
+    pub fn pixel_rgb888(&self, x: usize, y: usize) -> u32 {
+        let index = y * LCD_WIDTH + x;
+        self.rgb888(self.shades[index], self.colors[index])
+    }
+
+    //Iterates the frame's pixels, left to right and top to bottom, converting them as they are read
+    pub fn pixels(&self, format: PixelFormat) -> impl Iterator<Item = u32> + 'a {
+        let frame = *self;
+        (0..SCREEN_RESOLUTION)
+            .map(move |index| frame.convert_pixel(frame.shades[index], frame.colors[index], format))
+    }
+
+    //Copies the frame into an RGB888 image, scaled by a whole number
+    pub fn to_image(self, scale: usize) -> Image {
+        let mut image = Image::new(LCD_WIDTH, LCD_HEIGHT, 0);
+        for (index, pixel) in image.pixels.iter_mut().enumerate() {
+            *pixel = self.rgb888(self.shades[index], self.colors[index]);
+        }
+
+        image.scaled(scale)
+    }
+
+    //Converts the frame into a buffer owned by the caller, e.g. a display's DMA buffer.
+    //Returns the number of bytes written, 0 if the buffer is too small.
+    pub fn write_to(&self, format: PixelFormat, buffer: &mut [u8]) -> usize {
+        let size = format.frame_size();
+        if buffer.len() < size {
+            return 0;
+        }
+
+        if format == PixelFormat::Shades {
+            buffer[..size].copy_from_slice(self.shades());
+            return size;
+        }
+
+        let bytes_per_pixel = format.bytes_per_pixel();
+        for (pixel, out) in self.pixels(format).zip(buffer.chunks_exact_mut(bytes_per_pixel)) {
+            match format {
+                PixelFormat::Rgb565 => out.copy_from_slice(&(pixel as u16).to_be_bytes()),
+                _ => out.copy_from_slice(&pixel.to_be_bytes()),
+            }
+        }
+
+        //return
+        size
+    }
+}
+
+//Private methods
+impl<'a> Frame<'a> {
+    #[inline]
+    fn rgb888(&self, shade: u8, rgb555: u16) -> u32 {
+        if self.dmg {
+            self.profile.shade_to_rgb888(shade)
+        } else {
+            self.profile.rgb555_to_rgb888(rgb555)
+        }
+    }
+
+    fn convert_pixel(&self, shade: u8, rgb555: u16, format: PixelFormat) -> u32 {
+        if format == PixelFormat::Shades {
+            return shade as u32;
+        }
+
+        let rgb = self.rgb888(shade, rgb555);
+        let r = (rgb >> 16) & 0xff;
+        let g = (rgb >> 8) & 0xff;
+        let b = rgb & 0xff;
+
+        match format {
+            PixelFormat::Rgba8888 => (rgb << 8) | 0xff,
+            PixelFormat::Argb8888 => 0xff00_0000 | rgb,
+            PixelFormat::Rgb565 => ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3),
+            PixelFormat::Shades => shade as u32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_to_converts_every_pixel_in_the_requested_format() {
+        let profile = ColorProfile::new(); //Greyscale DMG palette
+        let mut shades = [0u8; SCREEN_RESOLUTION];
+        shades[1] = 1;
+        shades[SCREEN_RESOLUTION - 1] = 3;
+        let colors = [0u16; SCREEN_RESOLUTION];
+        let frame = Frame::new(&shades, &colors, true, &profile);
+
+        let mut buffer = vec![0; PixelFormat::Rgb565.frame_size()];
+        assert_eq!(frame.write_to(PixelFormat::Rgb565, &mut buffer), SCREEN_RESOLUTION * 2);
+        assert_eq!(buffer[..4], [0xff, 0xff, 0xad, 0x55]);
+        assert_eq!(buffer[buffer.len() - 2..], [0x00, 0x00]);
+
+        let mut buffer = vec![0; PixelFormat::Argb8888.frame_size()];
+        frame.write_to(PixelFormat::Argb8888, &mut buffer);
+        assert_eq!(buffer[4..8], [0xff, 0xaa, 0xaa, 0xaa]);
+
+        let mut buffer = vec![0; PixelFormat::Shades.frame_size()];
+        frame.write_to(PixelFormat::Shades, &mut buffer);
+        assert_eq!(buffer[..], shades[..]);
+
+        let mut too_small = vec![0; PixelFormat::Rgba8888.frame_size() - 1];
+        assert_eq!(frame.write_to(PixelFormat::Rgba8888, &mut too_small), 0);
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+use std::vec::Vec;
+
+use crate::cpu::*;
+use crate::memory_bank_controller::BANK_SIZE;
+use crate::ppu::DOTS_PER_FRAME;
+
+//Game Boy Sound System files: https://ocremix.org/info/GBS_Format_Specification
+//A GBS holds the sound driver and music of a game.  Its init routine starts a song and its play routine is then
+//called 60 times a second from VBlank, or from the timer interrupt with the rate stored in the header.
+
+const HEADER_SIZE: usize = 0x70;
+const MAGIC: &[u8] = b"GBS";
+const VERSION: u8 = 1;
+const TEXT_SIZE: usize = 32;
+
+//Header offsets
+const VERSION_OFFSET: usize = 0x03;
+const SONG_COUNT_OFFSET: usize = 0x04;
+const FIRST_SONG_OFFSET: usize = 0x05;
+const LOAD_ADDRESS_OFFSET: usize = 0x06;
+const INIT_ADDRESS_OFFSET: usize = 0x08;
+const PLAY_ADDRESS_OFFSET: usize = 0x0a;
+const STACK_POINTER_OFFSET: usize = 0x0c;
+const TIMER_MODULO_OFFSET: usize = 0x0e;
+const TIMER_CONTROL_OFFSET: usize = 0x0f;
+const TITLE_OFFSET: usize = 0x10;
+const AUTHOR_OFFSET: usize = 0x30;
+const COPYRIGHT_OFFSET: usize = 0x50;
+
+const TAC_ENABLE: u8 = 0x04;
+pub const TAC_DOUBLE_SPEED: u8 = 0x80; //Set when the song expects the CGB's double speed mode
+
+//The space below the load address holds the code driving the song.  The rst vectors jump to the ones the GBS
+//relocated to its load address, the VBlank and timer vectors call the play routine and init returns to an idle loop.
+const MIN_LOAD_ADDRESS: u16 = 0x0400;
+const RST_VECTORS: u16 = 8;
+const RST_SPACING: u16 = 8;
+pub const IDLE_LOOP_ADDR: u16 = 0x0068;
+
+//Opcodes used by the driver
+const JP: u8 = 0xc3;
+const CALL: u8 = 0xcd;
+const RETI: u8 = 0xd9;
+const EI: u8 = 0xfb;
+const JR: u8 = 0x18;
+
+pub struct GbsFile {
+    pub song_count: u8,
+    pub first_song: u8, //1 based
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub stack_pointer: u16,
+    pub timer_modulo: u8,
+    pub timer_control: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+    data: Vec<u8>, //Loaded at load_address
+}
+
+impl GbsFile {
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read(path)?)
+    }
+
+    pub fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_SIZE || &bytes[..MAGIC.len()] != MAGIC {
+            return Err(invalid("not a GBS file"));
+        }
+        if bytes[VERSION_OFFSET] != VERSION {
+            return Err(invalid("unsupported GBS version"));
+        }
+
+        let word = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let text = |offset: usize| {
+            let field = &bytes[offset..offset + TEXT_SIZE];
+            let end = field.iter().position(|byte| *byte == 0).unwrap_or(TEXT_SIZE);
+            String::from_utf8_lossy(&field[..end]).into_owned()
+        };
+
+        let gbs = Self {
+            song_count: bytes[SONG_COUNT_OFFSET],
+            first_song: bytes[FIRST_SONG_OFFSET],
+            load_address: word(LOAD_ADDRESS_OFFSET),
+            init_address: word(INIT_ADDRESS_OFFSET),
+            play_address: word(PLAY_ADDRESS_OFFSET),
+            stack_pointer: word(STACK_POINTER_OFFSET),
+            timer_modulo: bytes[TIMER_MODULO_OFFSET],
+            timer_control: bytes[TIMER_CONTROL_OFFSET],
+            title: text(TITLE_OFFSET),
+            author: text(AUTHOR_OFFSET),
+            copyright: text(COPYRIGHT_OFFSET),
+            data: bytes[HEADER_SIZE..].to_vec(),
+        };
+
+        if gbs.song_count == 0 {
+            return Err(invalid("the GBS has no songs"));
+        }
+        if gbs.load_address < MIN_LOAD_ADDRESS || gbs.load_address as usize >= 2 * BANK_SIZE {
+            return Err(invalid("the load address is outside of rom"));
+        }
+
+        Ok(gbs)
+    }
+
+    //True when the play routine is called by the timer interrupt instead of VBlank
+    pub fn uses_timer(&self) -> bool {
+        self.timer_control & TAC_ENABLE > 0
+    }
+
+    //True when the song runs the cpu in the CGB's double speed mode, which also doubles the timer's rate
+    pub fn double_speed(&self) -> bool {
+        self.timer_control & TAC_DOUBLE_SPEED > 0
+    }
+
+    //Calls to the play routine per second
+    pub fn play_rate(&self) -> f64 {
+        if !self.uses_timer() {
+            return CPU_CLOCK_HZ as f64 / DOTS_PER_FRAME as f64;
+        }
+
+        let tima_rate = match self.timer_control & 0x03 {
+            0 => 4096,
+            1 => 262144,
+            2 => 65536,
+            _ => 16384,
+        };
+        let speed = if self.double_speed() { 2 } else { 1 };
+
+        (tima_rate * speed) as f64 / (256 - self.timer_modulo as u32) as f64
+    }
+
+    //The cartridge rom the song runs from, split in banks selected by writes to 0x2000-0x3fff
+    pub fn rom_image(&self) -> Vec<u8> {
+        let load_address = self.load_address as usize;
+        let size = (load_address + self.data.len()).div_ceil(BANK_SIZE).max(2) * BANK_SIZE;
+        let mut image = vec![0; size];
+        image[load_address..load_address + self.data.len()].copy_from_slice(&self.data);
+
+        for vector in 0..RST_VECTORS {
+            let address = (vector * RST_SPACING) as usize;
+            image[address] = JP;
+            image[address + 1..address + 3].copy_from_slice(&(self.load_address + address as u16).to_le_bytes());
+        }
+
+        for vector in [V_BLANK_ADDR, LCD_STAT_ADDR, TIMER_ADDR, SERIAL_ADDR, JOYPAD_ADDR] {
+            image[vector as usize] = RETI;
+        }
+        let play_vector = if self.uses_timer() { TIMER_ADDR } else { V_BLANK_ADDR } as usize;
+        image[play_vector] = CALL;
+        image[play_vector + 1..play_vector + 3].copy_from_slice(&self.play_address.to_le_bytes());
+        image[play_vector + 3] = RETI;
+
+        //ei, jr back to the ei
+        let idle = IDLE_LOOP_ADDR as usize;
+        image[idle..idle + 3].copy_from_slice(&[EI, JR, 0xfd]);
+
+        image
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::vec::Vec;
+
+//Minimal animated GIF encoder: https://www.w3.org/Graphics/GIF/spec-gif89a.txt
+//Every frame is a full screen image.  The palette is global when all frames fit in 256 colors, which is always the
+//case for DMG games.  Otherwise each frame gets its own color table, and frames with more than 256 colors (CGB games
+//changing palettes mid frame) are quantized to a fixed color cube.
+
+const MAX_COLORS: usize = 256;
+const MAX_CODE: u16 = 4095; //12 bit LZW codes
+const MAX_SUB_BLOCK: usize = 255;
+const CUBE_LEVELS: [u32; 3] = [6, 7, 6]; //Red, green and blue steps of the fallback palette
+
+pub struct GifFrame<'a> {
+    pub pixels: &'a [u32], //RGB888
+    pub delay_centiseconds: u16,
+}
+
+//Encodes frames of width x height RGB888 pixels as a looping GIF
+pub fn encode(width: usize, height: usize, frames: &[GifFrame]) -> Vec<u8> {
+    let mut gif = Vec::new();
+    gif.extend_from_slice(b"GIF89a");
+    gif.extend_from_slice(&(width as u16).to_le_bytes());
+    gif.extend_from_slice(&(height as u16).to_le_bytes());
+
+    let global_palette = build_palette(frames.iter().flat_map(|frame| frame.pixels.iter()));
+    match &global_palette {
+        Some(palette) => {
+            gif.push(0x80 | table_size_bits(palette.len())); //Global color table follows
+            gif.extend_from_slice(&[0, 0]); //Background color, aspect ratio
+            write_color_table(&mut gif, palette);
+        }
+        None => gif.extend_from_slice(&[0, 0, 0]),
+    }
+
+    //Loop forever
+    gif.extend_from_slice(&[0x21, 0xff, 0x0b]);
+    gif.extend_from_slice(b"NETSCAPE2.0");
+    gif.extend_from_slice(&[0x03, 0x01, 0x00, 0x00, 0x00]);
+
+    for frame in frames {
+        //Graphic control extension with the frame's delay
+        gif.extend_from_slice(&[0x21, 0xf9, 0x04, 0x00]);
+        gif.extend_from_slice(&frame.delay_centiseconds.to_le_bytes());
+        gif.extend_from_slice(&[0x00, 0x00]);
+
+        //Image descriptor
+        gif.push(0x2c);
+        gif.extend_from_slice(&[0, 0, 0, 0]);
+        gif.extend_from_slice(&(width as u16).to_le_bytes());
+        gif.extend_from_slice(&(height as u16).to_le_bytes());
+
+        let local_palette;
+        let palette = match &global_palette {
+            Some(palette) => {
+                gif.push(0);
+                palette
+            }
+            None => {
+                local_palette = build_palette(frame.pixels.iter()).unwrap_or_else(color_cube);
+                gif.push(0x80 | table_size_bits(local_palette.len()));
+                write_color_table(&mut gif, &local_palette);
+                &local_palette
+            }
+        };
+
+        let lookup: HashMap<u32, u8> = palette.iter().enumerate().map(|(i, color)| (*color, i as u8)).collect();
+        let indexes: Vec<u8> = frame
+            .pixels
+            .iter()
+            .map(|color| match lookup.get(color) {
+                Some(index) => *index,
+                None => cube_index(*color),
+            })
+            .collect();
+
+        let min_code_size = (table_size_bits(palette.len()) + 1).max(2);
+        gif.push(min_code_size);
+        for block in lzw_compress(&indexes, min_code_size).chunks(MAX_SUB_BLOCK) {
+            gif.push(block.len() as u8);
+            gif.extend_from_slice(block);
+        }
+        gif.push(0);
+    }
+
+    gif.push(0x3b);
+
+    //return
+    gif
+}
+
+//Distinct colors in order of appearance, None if there are more than 256
+fn build_palette<'a>(pixels: impl Iterator<Item = &'a u32>) -> Option<Vec<u32>> {
+    let mut palette = Vec::new();
+    let mut seen = HashMap::new();
+    for color in pixels {
+        if !seen.contains_key(color) {
+            if palette.len() == MAX_COLORS {
+                return None;
+            }
+            seen.insert(*color, ());
+            palette.push(*color);
+        }
+    }
+
+    if palette.is_empty() {
+        palette.push(0);
+    }
+
+    Some(palette)
+}
+
+fn color_cube() -> Vec<u32> {
+    let mut palette = Vec::new();
+    for r in 0..CUBE_LEVELS[0] {
+        for g in 0..CUBE_LEVELS[1] {
+            for b in 0..CUBE_LEVELS[2] {
+                let scale = |level: u32, levels: u32| level * 255 / (levels - 1);
+                palette.push(
+                    (scale(r, CUBE_LEVELS[0]) << 16) | (scale(g, CUBE_LEVELS[1]) << 8) | scale(b, CUBE_LEVELS[2]),
+                );
+            }
+        }
+    }
+
+    palette
+}
+
+//Nearest entry of the color cube
+fn cube_index(color: u32) -> u8 {
+    let level = |channel: u32, levels: u32| (channel * (levels - 1) + 127) / 255;
+    let r = level((color >> 16) & 0xff, CUBE_LEVELS[0]);
+    let g = level((color >> 8) & 0xff, CUBE_LEVELS[1]);
+    let b = level(color & 0xff, CUBE_LEVELS[2]);
+
+    ((r * CUBE_LEVELS[1] + g) * CUBE_LEVELS[2] + b) as u8
+}
+
+//The color table size is stored as n where the table holds 2^(n + 1) colors
+fn table_size_bits(colors: usize) -> u8 {
+    let mut bits = 0;
+    while (2usize << bits) < colors {
+        bits += 1;
+    }
+
+    bits
+}
+
+fn write_color_table(gif: &mut Vec<u8>, palette: &[u32]) {
+    let size = 2usize << table_size_bits(palette.len());
+    for index in 0..size {
+        let color = palette.get(index).copied().unwrap_or(0);
+        gif.extend_from_slice(&color.to_be_bytes()[1..]);
+    }
+}
+
+//Variable length LZW as used by GIF.  Codes are packed least significant bit first.
+fn lzw_compress(indexes: &[u8], min_code_size: u8) -> Vec<u8> {
+    let clear_code: u16 = 1 << min_code_size;
+    let end_code = clear_code + 1;
+
+    let mut output = BitWriter::new();
+    let mut dictionary: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+
+    output.write(clear_code, code_size);
+
+    let mut pixels = indexes.iter();
+    let mut prefix = match pixels.next() {
+        Some(first) => *first as u16,
+        None => {
+            output.write(end_code, code_size);
+            return output.finish();
+        }
+    };
+
+    for pixel in pixels {
+        if let Some(code) = dictionary.get(&(prefix, *pixel)) {
+            prefix = *code;
+            continue;
+        }
+
+        output.write(prefix, code_size);
+
+        if next_code <= MAX_CODE {
+            dictionary.insert((prefix, *pixel), next_code);
+            //The decoder grows its code size once the next code no longer fits
+            if next_code == (1 << code_size) && code_size < 12 {
+                code_size += 1;
+            }
+            next_code += 1;
+        } else {
+            output.write(clear_code, code_size);
+            dictionary.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        }
+
+        prefix = *pixel as u16;
+    }
+
+    output.write(prefix, code_size);
+    output.write(end_code, code_size);
+
+    //return
+    output.finish()
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn write(&mut self, code: u16, size: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += size;
+        while self.bits >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+
+        self.bytes
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs;
+use std::io;
+use std::vec::Vec;
+
+use crate::png;
+
+//RGB888 image used by the debug views
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u32>, //0x00RRGGBB, left to right and top to bottom
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, color: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![color; width * height],
+        }
+    }
+
+    //Pixels outside of the image are ignored
+    #[inline]
+    pub fn set_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+    }
+
+    //Nearest neighbour scaling by a whole number so pixels stay sharp.  A factor of 0 is treated as 1.
+    pub fn scaled(&self, factor: usize) -> Image {
+        let factor = factor.max(1);
+        let mut image = Image::new(self.width * factor, self.height * factor, 0);
+
+        for (y, row) in image.pixels.chunks_mut(self.width * factor).enumerate() {
+            let source = &self.pixels[(y / factor) * self.width..(y / factor + 1) * self.width];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = source[x / factor];
+            }
+        }
+
+        //return
+        image
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        png::encode_rgb(self.width, self.height, &self.pixels)
+    }
+
+    pub fn save_png(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_png())
+    }
+}
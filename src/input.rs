@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use crate::joypad::*;
+
+//Input sources the cpu polls once per frame, see InputSource in joypad.rs
+
+//Pairs of directions the d-pad can't press together.  Some games crash when they read both.
+const OPPOSING_DIRECTIONS: [(Button, Button); 2] = [(Button::Left, Button::Right), (Button::Up, Button::Down)];
+
+const COMMENT: char = '#';
+const NO_BUTTONS: &str = "-"; //Scripts use it to release every button
+
+//Frontend actions bound to keys alongside the buttons
+#[derive(Copy, Clone, PartialEq)]
+pub enum Hotkey {
+    Screenshot,
+    SaveGif,
+    ToggleMute(usize), //Sound channel 1-4
+}
+
+impl Hotkey {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "screenshot" => Some(Hotkey::Screenshot),
+            "gif" => Some(Hotkey::SaveGif),
+            name => match name
+                .strip_prefix("mute")
+                .and_then(|channel| channel.parse::<usize>().ok())
+            {
+                Some(channel) if (1..=4).contains(&channel) => Some(Hotkey::ToggleMute(channel)),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Binding {
+    Button(Button),
+    Hotkey(Hotkey),
+}
+
+impl Binding {
+    fn from_name(name: &str) -> Option<Self> {
+        Button::from_name(name)
+            .map(Binding::Button)
+            .or_else(|| Hotkey::from_name(name).map(Binding::Hotkey))
+    }
+}
+
+//Which keys press which buttons or hotkeys.  Keys are the frontend's names for them, compared without case.
+pub struct KeyBindings {
+    keys: HashMap<String, Binding>,
+}
+
+impl KeyBindings {
+    //Arrow keys for the d-pad, x and z for A and B, enter for start and backspace for select.  f12 saves a screenshot,
+    //f11 a GIF of the last seconds and 1 to 4 mute or unmute the sound channels.
+    pub fn new() -> Self {
+        let mut bindings = Self { keys: HashMap::new() };
+        for (key, binding) in [
+            ("right", Binding::Button(Button::Right)),
+            ("left", Binding::Button(Button::Left)),
+            ("up", Binding::Button(Button::Up)),
+            ("down", Binding::Button(Button::Down)),
+            ("x", Binding::Button(Button::A)),
+            ("z", Binding::Button(Button::B)),
+            ("backspace", Binding::Button(Button::Select)),
+            ("enter", Binding::Button(Button::Start)),
+            ("f12", Binding::Hotkey(Hotkey::Screenshot)),
+            ("f11", Binding::Hotkey(Hotkey::SaveGif)),
+            ("1", Binding::Hotkey(Hotkey::ToggleMute(1))),
+            ("2", Binding::Hotkey(Hotkey::ToggleMute(2))),
+            ("3", Binding::Hotkey(Hotkey::ToggleMute(3))),
+            ("4", Binding::Hotkey(Hotkey::ToggleMute(4))),
+        ] {
+            bindings.bind(key, binding);
+        }
+
+        bindings
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    //One button or hotkey per line with the keys that press it, e.g. "a = x, space" or "screenshot = p".  Lines
+    //starting with # are comments.  Buttons and hotkeys not in the file keep their default keys.
+    pub fn parse(config: &str) -> io::Result<Self> {
+        let mut bindings = Self::new();
+        let mut rebound = Vec::new();
+
+        for (number, line) in lines(config) {
+            let (name, keys) = line
+                .split_once('=')
+                .ok_or_else(|| invalid(number, "expected <button> = <keys>"))?;
+            let binding =
+                Binding::from_name(name.trim()).ok_or_else(|| invalid(number, "unknown button or hotkey"))?;
+
+            //The first line for a button replaces its default keys
+            if !rebound.contains(&binding) {
+                bindings.keys.retain(|_, bound| *bound != binding);
+                rebound.push(binding);
+            }
+
+            for key in keys.split(',').map(|key| key.trim()).filter(|key| !key.is_empty()) {
+                bindings.bind(key, binding);
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    fn bind(&mut self, key: &str, binding: Binding) {
+        self.keys.insert(key.to_ascii_lowercase(), binding);
+    }
+
+    fn binding(&self, key: &str) -> Option<Binding> {
+        self.keys.get(&key.to_ascii_lowercase()).copied()
+    }
+}
+
+//Buttons held on the keyboard.  The frontend sends its key events to KeyEvents while the cpu polls KeyboardInput.
+//A key pressed and released between two polls still shows up for one frame.
+struct KeyState {
+    held: Buttons,
+    tapped: Buttons,      //Pressed since the last poll
+    hotkeys: Vec<Hotkey>, //Pressed since the frontend last took them
+}
+
+pub struct KeyboardInput {
+    state: Arc<Mutex<KeyState>>,
+}
+
+#[derive(Clone)]
+pub struct KeyEvents {
+    bindings: Arc<KeyBindings>,
+    state: Arc<Mutex<KeyState>>,
+}
+
+//Creates the two ends of the keyboard input
+pub fn keyboard(bindings: KeyBindings) -> (KeyboardInput, KeyEvents) {
+    let state = Arc::new(Mutex::new(KeyState {
+        held: Buttons::default(),
+        tapped: Buttons::default(),
+        hotkeys: Vec::new(),
+    }));
+
+    (
+        KeyboardInput { state: state.clone() },
+        KeyEvents {
+            bindings: Arc::new(bindings),
+            state,
+        },
+    )
+}
+
+impl InputSource for KeyboardInput {
+    fn poll(&mut self) -> Buttons {
+        let mut state = self.state.lock().unwrap();
+        let buttons = state.held.union(state.tapped);
+        state.tapped = Buttons::default();
+
+        buttons
+    }
+
+    fn boot_buttons(&mut self) -> Buttons {
+        self.state.lock().unwrap().held
+    }
+}
+
+impl KeyEvents {
+    //Keys without a binding are ignored
+    pub fn key_down(&self, key: &str) {
+        let mut state = self.state.lock().unwrap();
+        match self.bindings.binding(key) {
+            Some(Binding::Button(button)) => {
+                state.held.set(button, true);
+                state.tapped.set(button, true);
+            }
+            Some(Binding::Hotkey(hotkey)) => state.hotkeys.push(hotkey),
+            None => {}
+        }
+    }
+
+    pub fn key_up(&self, key: &str) {
+        if let Some(Binding::Button(button)) = self.bindings.binding(key) {
+            self.state.lock().unwrap().held.set(button, false);
+        }
+    }
+
+    //The hotkeys pressed since the last call, in order
+    pub fn take_hotkeys(&self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.state.lock().unwrap().hotkeys)
+    }
+}
+
+//Plays back buttons from a script, for reproducing bugs and automated runs.  Each line has the frame the buttons
+//are pressed on and the buttons joined by +, e.g. "120 start" or "300 right+a".  The buttons stay held until the
+//next line, "-" releases them all.  Frames are numbered from 1 like --screenshot, frame 0 holds buttons while the
+//boot rom runs, e.g. "0 left+a" to pick a CGB compatibility palette.
+pub struct ScriptedInput {
+    steps: Vec<(u32, Buttons)>,
+    next_step: usize,
+    frame: u32,
+    buttons: Buttons,
+}
+
+impl ScriptedInput {
+    pub fn load(path: &str) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(script: &str) -> io::Result<Self> {
+        let mut steps: Vec<(u32, Buttons)> = Vec::new();
+
+        for (number, line) in lines(script) {
+            let (frame, names) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid(number, "expected <frame> <buttons>"))?;
+            let frame = frame
+                .parse::<u32>()
+                .map_err(|_| invalid(number, "the frame must be a whole number"))?;
+            if steps.last().is_some_and(|(last, _)| *last >= frame) {
+                return Err(invalid(number, "frames must be in increasing order"));
+            }
+
+            let mut buttons = Buttons::default();
+            let names = names.trim();
+            if names != NO_BUTTONS {
+                for name in names.split('+') {
+                    let button =
+                        Button::from_name(name.trim()).ok_or_else(|| invalid(number, "unknown button"))?;
+                    buttons.set(button, true);
+                }
+            }
+
+            steps.push((frame, buttons));
+        }
+
+        Ok(Self {
+            steps,
+            next_step: 0,
+            frame: 0,
+            buttons: Buttons::default(),
+        })
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self) -> Buttons {
+        self.frame += 1;
+        while let Some((frame, buttons)) = self.steps.get(self.next_step) {
+            if *frame > self.frame {
+                break;
+            }
+            self.buttons = *buttons;
+            self.next_step += 1;
+        }
+
+        self.buttons
+    }
+
+    fn boot_buttons(&mut self) -> Buttons {
+        match self.steps.first() {
+            Some((0, buttons)) => *buttons,
+            _ => Buttons::default(),
+        }
+    }
+}
+
+//Releases both buttons of an opposing pair, Left+Right or Up+Down, when another source presses them together
+pub struct BlockOpposing {
+    source: Box<dyn InputSource>,
+}
+
+impl BlockOpposing {
+    pub fn new(source: Box<dyn InputSource>) -> Self {
+        Self { source }
+    }
+}
+
+impl InputSource for BlockOpposing {
+    fn poll(&mut self) -> Buttons {
+        let mut buttons = self.source.poll();
+        for (first, second) in OPPOSING_DIRECTIONS {
+            if buttons.is_pressed(first) && buttons.is_pressed(second) {
+                buttons.set(first, false);
+                buttons.set(second, false);
+            }
+        }
+
+        buttons
+    }
+
+    fn boot_buttons(&mut self) -> Buttons {
+        self.source.boot_buttons()
+    }
+}
+
+//The lines of a config or script with their line numbers, skipping blank lines and comments
+fn lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with(COMMENT))
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(buttons: &[Button]) -> Buttons {
+        let mut pressed = Buttons::default();
+        for button in buttons {
+            pressed.set(*button, true);
+        }
+        pressed
+    }
+
+    #[test]
+    fn bindings_replace_the_defaults_of_the_buttons_they_list() {
+        let bindings = KeyBindings::parse("# comment\na = space, J\n\na = k\nscreenshot = p").unwrap();
+        assert!(bindings.binding("x").is_none());
+        assert!(bindings.binding("space") == Some(Binding::Button(Button::A)));
+        assert!(bindings.binding("j") == Some(Binding::Button(Button::A)));
+        assert!(bindings.binding("k") == Some(Binding::Button(Button::A)));
+        assert!(bindings.binding("Enter") == Some(Binding::Button(Button::Start)));
+        assert!(bindings.binding("p") == Some(Binding::Hotkey(Hotkey::Screenshot)));
+        assert!(bindings.binding("f12").is_none());
+
+        assert!(KeyBindings::parse("jump = space").is_err());
+        assert!(KeyBindings::parse("a space").is_err());
+    }
+
+    #[test]
+    fn a_tapped_key_is_seen_by_one_poll() {
+        let (mut keyboard, events) = keyboard(KeyBindings::new());
+        events.key_down("x");
+        events.key_up("x");
+        events.key_down("up");
+
+        assert!(keyboard.poll() == pressed(&[Button::A, Button::Up]));
+        assert!(keyboard.poll() == pressed(&[Button::Up]));
+        events.key_up("up");
+        assert!(keyboard.poll() == Buttons::default());
+    }
+
+    #[test]
+    fn hotkeys_are_queued_for_the_frontend() {
+        let (mut keyboard, events) = keyboard(KeyBindings::new());
+        events.key_down("F12");
+        events.key_up("F12");
+
+        assert!(keyboard.poll() == Buttons::default());
+        assert!(events.take_hotkeys() == [Hotkey::Screenshot]);
+        assert!(events.take_hotkeys().is_empty());
+    }
+
+    #[test]
+    fn mute_hotkeys_name_a_channel() {
+        assert!(Hotkey::from_name("Mute3") == Some(Hotkey::ToggleMute(3)));
+        assert!(Hotkey::from_name("mute0").is_none());
+        assert!(Hotkey::from_name("mute5").is_none());
+        assert!(KeyBindings::new().binding("2") == Some(Binding::Hotkey(Hotkey::ToggleMute(2))));
+    }
+
+    #[test]
+    fn scripted_buttons_stay_held_until_the_next_line() {
+        let mut script = ScriptedInput::parse("2 start\n4 right+a\n5 -\n").unwrap();
+        let frames: Vec<Buttons> = (0..5).map(|_| script.poll()).collect();
+        assert!(
+            frames
+                == [
+                    Buttons::default(),
+                    pressed(&[Button::Start]),
+                    pressed(&[Button::Start]),
+                    pressed(&[Button::Right, Button::A]),
+                    Buttons::default(),
+                ]
+        );
+
+        assert!(ScriptedInput::parse("4 a\n2 b").is_err());
+        assert!(ScriptedInput::parse("x a").is_err());
+    }
+
+    #[test]
+    fn frame_0_of_a_script_is_held_at_boot() {
+        let mut script = ScriptedInput::parse("0 left+a\n2 -").unwrap();
+        assert!(script.boot_buttons() == pressed(&[Button::Left, Button::A]));
+        assert!(script.poll() == pressed(&[Button::Left, Button::A]));
+        assert!(script.poll() == Buttons::default());
+
+        let mut script = ScriptedInput::parse("1 left+a").unwrap();
+        assert!(script.boot_buttons() == Buttons::default());
+    }
+
+    #[test]
+    fn opposing_directions_are_released_together() {
+        let script = ScriptedInput::parse("1 left+right+up+a").unwrap();
+        let mut input = BlockOpposing::new(Box::new(script));
+        assert!(input.poll() == pressed(&[Button::Up, Button::A]));
+    }
+}
@@ -5,6 +5,12 @@ const Z_BITS_MASK: u8 = 0x07;
 const P_REG_MASK: u8 = 0x30;
 const LDH_ADDR_MSB_MASK: usize = 0xff00;
 
+//Extra M-cycles conditional instructions take when the condition is met.  The opcode table has the untaken timing.
+const JR_TAKEN_M_CYCLES: u8 = 1;
+const JP_TAKEN_M_CYCLES: u8 = 1;
+const CALL_TAKEN_M_CYCLES: u8 = 3;
+const RET_TAKEN_M_CYCLES: u8 = 3;
+
 pub struct Opcode {
     opcode_byte: u8, //hex representatoin of the opcode
     pub opcode_name: String,
@@ -736,7 +742,7 @@ impl Opcode {
     //low power standby mode (VERY low power)
     //0b00010000/0x10
     pub fn stop(&self, cpu: &mut Cpu) {
-        //place holder
+        cpu.set_stop();
     }
 
     //disable interrupts, IME=0
@@ -780,6 +786,7 @@ impl Opcode {
 
         if jump {
             self.jp_nn(cpu);
+            cpu.add_branch_cycles(JP_TAKEN_M_CYCLES);
         }
     }
 
@@ -804,6 +811,7 @@ impl Opcode {
 
         if jump {
             self.jr_dd(cpu);
+            cpu.add_branch_cycles(JR_TAKEN_M_CYCLES);
         }
     }
 
@@ -825,6 +833,7 @@ impl Opcode {
 
         if call {
             self.call_nn(cpu);
+            cpu.add_branch_cycles(CALL_TAKEN_M_CYCLES);
         }
     }
 
@@ -854,6 +863,7 @@ impl Opcode {
 
         if ret {
             self.ret(cpu);
+            cpu.add_branch_cycles(RET_TAKEN_M_CYCLES);
         }
     }
 
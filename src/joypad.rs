@@ -0,0 +1,203 @@
+pub const JOYPAD_REG: usize = 0xff00;
+
+//P1/JOYP: https://gbdev.io/pandocs/Joypad_Input.html
+//The 8 buttons are wired as a 2x4 matrix.  Writing 0 to bit 4 selects the d-pad and writing 0 to bit 5 selects the
+//buttons, then bits 0-3 read 0 for every selected button held down.  A selected line going from high to low requests
+//the joypad interrupt and wakes the cpu from STOP.
+const SELECT_DIRECTIONS: u8 = 0x10;
+const SELECT_ACTIONS: u8 = 0x20;
+const SELECT_BITS: u8 = SELECT_DIRECTIONS | SELECT_ACTIONS;
+const INPUT_LINES: u8 = 0x0f;
+const UNUSED_BITS: u8 = 0xc0; //Read back as 1
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Button {
+    Right = 0,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "right" => Some(Button::Right),
+            "left" => Some(Button::Left),
+            "up" => Some(Button::Up),
+            "down" => Some(Button::Down),
+            "a" => Some(Button::A),
+            "b" => Some(Button::B),
+            "select" => Some(Button::Select),
+            "start" => Some(Button::Start),
+            _ => None,
+        }
+    }
+}
+
+//The buttons held down, one bit per button.  The low nibble is the d-pad and the high nibble the buttons, in the
+//order of P1's input lines.
+#[derive(Copy, Clone, Default, PartialEq)]
+pub struct Buttons {
+    pressed: u8,
+}
+
+impl Buttons {
+    pub fn set(&mut self, button: Button, pressed: bool) {
+        if pressed {
+            self.pressed |= 1 << button as u8;
+        } else {
+            self.pressed &= !(1 << button as u8);
+        }
+    }
+
+    pub fn is_pressed(&self, button: Button) -> bool {
+        self.pressed & (1 << button as u8) > 0
+    }
+
+    //The buttons pressed in either set
+    pub fn union(&self, other: Buttons) -> Buttons {
+        Buttons {
+            pressed: self.pressed | other.pressed,
+        }
+    }
+
+    fn directions(&self) -> u8 {
+        self.pressed & INPUT_LINES
+    }
+
+    fn actions(&self) -> u8 {
+        self.pressed >> 4
+    }
+}
+
+//Implemented by the frontend to feed the joypad.  Polled once per frame.
+pub trait InputSource {
+    fn poll(&mut self) -> Buttons;
+
+    //The buttons held while the boot rom runs, before the first frame.  The CGB boot rom reads them to pick a
+    //compatibility palette.
+    fn boot_buttons(&mut self) -> Buttons {
+        Buttons::default()
+    }
+}
+
+pub struct Joypad {
+    select: u8, //Bits 4 and 5 as last written
+    buttons: Buttons,
+}
+
+impl Joypad {
+    pub fn new() -> Self {
+        Self {
+            select: 0,
+            buttons: Buttons::default(),
+        }
+    }
+
+    pub fn read_register(&self) -> u8 {
+        UNUSED_BITS | self.select | self.input_lines()
+    }
+
+    //Returns true if the write pulled an input line low and requested an interrupt
+    pub fn write_register(&mut self, value: u8) -> bool {
+        let input_lines = self.input_lines();
+        self.select = value & SELECT_BITS;
+
+        input_lines & !self.input_lines() > 0
+    }
+
+    //Returns true if a newly pressed button pulled an input line low and requested an interrupt
+    pub fn set_buttons(&mut self, buttons: Buttons) -> bool {
+        let input_lines = self.input_lines();
+        self.buttons = buttons;
+
+        input_lines & !self.input_lines() > 0
+    }
+
+    //True while a selected button is held, which keeps the cpu out of STOP
+    pub fn any_selected_pressed(&self) -> bool {
+        self.input_lines() != INPUT_LINES
+    }
+}
+
+//Private methods
+impl Joypad {
+    //Active low, 0 for each selected button held down
+    fn input_lines(&self) -> u8 {
+        let mut pressed = 0;
+        if self.select & SELECT_DIRECTIONS == 0 {
+            pressed |= self.buttons.directions();
+        }
+        if self.select & SELECT_ACTIONS == 0 {
+            pressed |= self.buttons.actions();
+        }
+
+        INPUT_LINES & !pressed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SELECT_ONLY_DIRECTIONS: u8 = SELECT_ACTIONS; //The line written 0 is the one selected
+    const SELECT_ONLY_ACTIONS: u8 = SELECT_DIRECTIONS;
+
+    fn pressed(buttons: &[Button]) -> Buttons {
+        let mut pressed = Buttons::default();
+        for button in buttons {
+            pressed.set(*button, true);
+        }
+        pressed
+    }
+
+    #[test]
+    fn reads_the_buttons_of_the_selected_lines() {
+        let mut joypad = Joypad::new();
+        joypad.set_buttons(pressed(&[Button::Right, Button::A, Button::Start]));
+
+        joypad.write_register(SELECT_ONLY_DIRECTIONS);
+        assert_eq!(joypad.read_register(), 0xee);
+        joypad.write_register(SELECT_ONLY_ACTIONS);
+        assert_eq!(joypad.read_register(), 0xd6);
+        joypad.write_register(0);
+        assert_eq!(joypad.read_register(), 0xc6);
+        joypad.write_register(SELECT_BITS);
+        assert_eq!(joypad.read_register(), 0xff);
+    }
+
+    #[test]
+    fn pressing_a_selected_button_requests_the_interrupt() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(SELECT_ONLY_DIRECTIONS);
+
+        assert!(!joypad.set_buttons(pressed(&[Button::A])));
+        assert!(!joypad.any_selected_pressed());
+        assert!(joypad.set_buttons(pressed(&[Button::A, Button::Down])));
+        assert!(joypad.any_selected_pressed());
+
+        //Held buttons and releases don't pull a line low
+        assert!(!joypad.set_buttons(pressed(&[Button::A, Button::Down])));
+        assert!(!joypad.set_buttons(Buttons::default()));
+
+        //Left shares its line with B, which is already low when both groups are selected
+        joypad.write_register(0);
+        assert!(joypad.set_buttons(pressed(&[Button::B])));
+        assert!(!joypad.set_buttons(pressed(&[Button::B, Button::Left])));
+    }
+
+    #[test]
+    fn selecting_a_line_with_a_held_button_requests_the_interrupt() {
+        let mut joypad = Joypad::new();
+        joypad.write_register(SELECT_BITS);
+        assert!(!joypad.set_buttons(pressed(&[Button::Select])));
+
+        assert!(!joypad.write_register(SELECT_ONLY_DIRECTIONS));
+        assert!(joypad.write_register(SELECT_ONLY_ACTIONS));
+        assert!(!joypad.write_register(SELECT_ONLY_ACTIONS));
+    }
+}
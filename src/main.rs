@@ -1,17 +1,53 @@
+mod apu;
+mod apu_channels;
+mod audio_sink;
+mod color_profile;
+mod compat_palettes;
 mod cpu;
+mod debug_view;
+mod frame;
+mod gbs;
+mod gif;
+mod image;
+mod input;
 mod instructions;
+mod joypad;
 mod memory_bank_controller;
 mod opcode_table;
+mod pixel_fifo;
+mod png;
 mod ppu;
+mod recorder;
+mod resampler;
 mod rom;
 mod timer;
 mod user_interface;
+mod vgm;
 mod vram;
+mod wav;
 
 #[path = "Windows_Interface/windows_interface.rs"]
 mod windows_interface;
 
+use std::env;
+use std::fs::File;
+use std::io;
+
+const RENDER_SECONDS: u64 = 120; //Length of GBS renders without --seconds
+const STREAM_BUFFER_FRACTION: u32 = 5; //The --audio-stream ring buffer holds 1/5 of a second
+
+use crate::audio_sink::{ring_buffer, AudioSink, NullSink, WavSink};
+use crate::color_profile::{ColorCorrection, DmgPalette};
+use crate::compat_palettes::ManualPalette;
 use crate::cpu::*;
+use crate::frame::PixelFormat;
+use crate::gbs::GbsFile;
+use crate::image::Image;
+use crate::input::{BlockOpposing, Hotkey, KeyBindings, ScriptedInput};
+use crate::joypad::InputSource;
+use crate::ppu::Renderer;
+use crate::recorder::{GifCapture, RawVideo, Recorder, GIF_SECONDS};
+use crate::vram::{TILE_MAP_1_START, TILE_MAP_2_START};
 use opcode_table::OpcodeTable;
 use windows_interface::*;
 
@@ -22,7 +58,288 @@ fn main() {
     let mut windows = WindowsInterface::new();
 
     //get command line arguments
-    //let args: Vec<String> = env::args().collect();
+    let args: Vec<String> = env::args().collect();
+
+    //--fifo selects the cycle accurate pixel fifo renderer
+    if args.iter().any(|arg| arg == "--fifo") {
+        gameboy_cpu.set_renderer(Renderer::PixelFifo);
+    }
+
+    //--no-access-blocking lets the cpu access vram/oam during every ppu mode
+    if args.iter().any(|arg| arg == "--no-access-blocking") {
+        gameboy_cpu.set_vram_access_blocking(false);
+    }
+
+    //--cgb runs the rom on a CGB instead of a DMG
+    if args.iter().any(|arg| arg == "--cgb") {
+        gameboy_cpu.set_model(HardwareModel::Cgb);
+    }
+
+    //--palette <combo> picks a compatibility palette for DMG games on the CGB, e.g. --palette left+a.  Without it the
+    //combo is read from the buttons held at boot, e.g. a "0 left+a" line in the --input script.
+    if let Some(index) = args.iter().position(|arg| arg == "--palette") {
+        match args.get(index + 1).and_then(|name| ManualPalette::from_name(name)) {
+            Some(combo) => gameboy_cpu.set_boot_combo(Some(combo)),
+            None => println!("Unknown palette.  Use up, left, down or right, optionally with +a or +b."),
+        }
+    }
+
+    //--dmg-palette <name> colors the DMG shades: green, pocket, light, grey or 4 RGB hex colors separated by commas
+    let mut dmg_palette = DmgPalette::Greyscale;
+    if let Some(index) = args.iter().position(|arg| arg == "--dmg-palette") {
+        match args.get(index + 1).and_then(|name| DmgPalette::from_name(name)) {
+            Some(palette) => {
+                dmg_palette = palette;
+                gameboy_cpu.set_dmg_palette(palette);
+            }
+            None => println!("Unknown DMG palette.  Use green, pocket, light, grey or 4 hex colors."),
+        }
+    }
+
+    //--color-correction <mode> adjusts CGB colors: raw, lcd or gba
+    if let Some(index) = args.iter().position(|arg| arg == "--color-correction") {
+        match args.get(index + 1).and_then(|name| ColorCorrection::from_name(name)) {
+            Some(correction) => gameboy_cpu.set_color_correction(correction),
+            None => println!("Unknown color correction.  Use raw, lcd or gba."),
+        }
+    }
+
+    //--dump-tiles <path> writes every tile in vram to a PNG after each frame.  {frame} in the path is replaced with
+    //the frame number, otherwise the file always holds the latest frame.
+    let dump_tiles = args
+        .iter()
+        .position(|arg| arg == "--dump-tiles")
+        .and_then(|index| args.get(index + 1));
+
+    //--dump-map <path> does the same for the background map with the viewport and window outlined.
+    //--map 9800 or --map 9c00 picks the map, otherwise the map used by the background is drawn.
+    let dump_map = args
+        .iter()
+        .position(|arg| arg == "--dump-map")
+        .and_then(|index| args.get(index + 1));
+    let mut map_address = None;
+    if let Some(index) = args.iter().position(|arg| arg == "--map") {
+        match args
+            .get(index + 1)
+            .map(|address| address.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("9800") => map_address = Some(TILE_MAP_1_START),
+            Some("9c00") => map_address = Some(TILE_MAP_2_START),
+            _ => println!("Unknown tile map.  Use 9800 or 9c00."),
+        }
+    }
+
+    //--dump-sprites <path> writes the 40 sprites drawn on their own
+    let dump_sprites = args
+        .iter()
+        .position(|arg| arg == "--dump-sprites")
+        .and_then(|index| args.get(index + 1));
+
+    //--scale <n> scales screenshots by a whole number
+    if let Some(index) = args.iter().position(|arg| arg == "--scale") {
+        match args.get(index + 1).and_then(|scale| scale.parse::<usize>().ok()) {
+            Some(scale) if scale > 0 => windows.set_screenshot_scale(scale),
+            _ => println!("The screenshot scale must be a whole number greater than 0."),
+        }
+    }
+
+    //--screenshot <frame> saves a screenshot once that frame is drawn, e.g. for comparing test roms
+    let mut screenshot_frame = None;
+    if let Some(index) = args.iter().position(|arg| arg == "--screenshot") {
+        match args.get(index + 1).and_then(|frame| frame.parse::<u32>().ok()) {
+            Some(frame) => screenshot_frame = Some(frame),
+            None => println!("--screenshot needs the number of the frame to capture."),
+        }
+    }
+
+    //--sample-rate <hz> sets the rate of the audio output, e.g. 44100 or 48000
+    if let Some(index) = args.iter().position(|arg| arg == "--sample-rate") {
+        match args.get(index + 1).and_then(|rate| rate.parse::<u32>().ok()) {
+            Some(rate) if rate > 0 => gameboy_cpu.set_sample_rate(rate),
+            _ => println!("The sample rate must be a whole number of Hz, e.g. 44100 or 48000."),
+        }
+    }
+
+    //--record <path> records the game to <path>.y4m and <path>.wav.  --record-frames <n> stops the emulator after n
+    //frames so the files are closed properly.
+    let mut recorder = None;
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--record")
+        .and_then(|index| args.get(index + 1))
+    {
+        match Recorder::start(path, gameboy_cpu.sample_rate()) {
+            Ok(started) => recorder = Some(started),
+            Err(error) => println!("Could not start recording: {}", error),
+        }
+    }
+    let record_frames = args
+        .iter()
+        .position(|arg| arg == "--record-frames")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|frames| frames.parse::<u32>().ok());
+
+    //--raw-video <path> writes every frame to <path> without a header.  --pixel-format <format> picks shades,
+    //rgba8888, rgb565 or argb8888, rgba8888 otherwise.
+    let mut pixel_format = PixelFormat::Rgba8888;
+    if let Some(index) = args.iter().position(|arg| arg == "--pixel-format") {
+        match args.get(index + 1).and_then(|name| PixelFormat::from_name(name)) {
+            Some(format) => pixel_format = format,
+            None => println!("Unknown pixel format.  Use shades, rgba8888, rgb565 or argb8888."),
+        }
+    }
+    let mut raw_video = None;
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--raw-video")
+        .and_then(|index| args.get(index + 1))
+    {
+        match RawVideo::create(path, pixel_format) {
+            Ok(created) => raw_video = Some(created),
+            Err(error) => println!("Could not create {}: {}", path, error),
+        }
+    }
+
+    //--audio-out <path> writes the sound to a WAV file, --audio-out null runs without sound.  --frames <n> stops the
+    //emulator after n frames, so headless runs can be checked by hashing the WAV.
+    if let Some(output) = args
+        .iter()
+        .position(|arg| arg == "--audio-out")
+        .and_then(|index| args.get(index + 1))
+    {
+        if output == "null" {
+            gameboy_cpu.set_audio_sink(Box::new(NullSink));
+        } else {
+            match WavSink::create(output, gameboy_cpu.sample_rate()) {
+                Ok(sink) => gameboy_cpu.set_audio_sink(Box::new(sink)),
+                Err(error) => println!("Could not create {}: {}", output, error),
+            }
+        }
+    }
+    //--audio-stream <path> plays the sound into <path> as raw 16-bit stereo PCM while the game runs, through a ring
+    //buffer like a real-time frontend would, e.g. a named pipe read by "aplay -f S16_LE -c 2 -r 48000"
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--audio-stream")
+        .and_then(|index| args.get(index + 1))
+    {
+        match File::create(path) {
+            Ok(output) => {
+                let (sink, reader) =
+                    ring_buffer((gameboy_cpu.sample_rate() / STREAM_BUFFER_FRACTION * 2) as usize);
+                gameboy_cpu.set_audio_sink(Box::new(sink));
+                WindowsInterface::stream_audio(reader, output);
+            }
+            Err(error) => println!("Could not open {}: {}", path, error),
+        }
+    }
+    //--mute 1,3 takes channels out of the mix, --solo 2 keeps only that channel.  While running the mute hotkeys, 1 to 4
+    //by default, toggle each channel.
+    if let Some(channels) = args
+        .iter()
+        .position(|arg| arg == "--mute")
+        .and_then(|index| args.get(index + 1))
+    {
+        for channel in channels.split(',') {
+            match channel.parse::<usize>() {
+                Ok(channel) if (1..=4).contains(&channel) => gameboy_cpu.set_channel_muted(channel, true),
+                _ => println!("Unknown channel {}.  Use 1 to 4.", channel),
+            }
+        }
+    }
+    if let Some(index) = args.iter().position(|arg| arg == "--solo") {
+        match args.get(index + 1).and_then(|channel| channel.parse::<usize>().ok()) {
+            Some(channel) if (1..=4).contains(&channel) => gameboy_cpu.solo_channel(Some(channel)),
+            _ => println!("--solo needs a channel from 1 to 4."),
+        }
+    }
+
+    //--capture-channels <path> writes each channel to its own WAV, <path>_ch1.wav to <path>_ch4.wav
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--capture-channels")
+        .and_then(|index| args.get(index + 1))
+    {
+        let sinks: std::io::Result<Vec<Box<dyn AudioSink>>> = (1..=4)
+            .map(|channel| {
+                WavSink::create(&format!("{}_ch{}.wav", path, channel), gameboy_cpu.sample_rate())
+                    .map(|sink| Box::new(sink) as Box<dyn AudioSink>)
+            })
+            .collect();
+        match sinks {
+            Ok(sinks) => gameboy_cpu.set_channel_sinks(sinks),
+            Err(error) => println!("Could not start the channel capture: {}", error),
+        }
+    }
+
+    //Keys typed in the console press the buttons and hotkeys, see read_console_keys.  --keys <path> loads key
+    //bindings, one button or hotkey per line like "a = x, space".  --input <path> plays the buttons back from a script
+    //instead of the keyboard, the hotkeys still work.  --block-opposing stops Left+Right and Up+Down being pressed
+    //together.
+    let bindings = match args
+        .iter()
+        .position(|arg| arg == "--keys")
+        .and_then(|index| args.get(index + 1))
+    {
+        Some(path) => match KeyBindings::load(path) {
+            Ok(bindings) => bindings,
+            Err(error) => {
+                println!("Could not load {}: {}", path, error);
+                return;
+            }
+        },
+        None => KeyBindings::new(),
+    };
+    let (keyboard, key_events) = input::keyboard(bindings);
+    WindowsInterface::read_console_keys(key_events.clone());
+
+    let mut input: Box<dyn InputSource> = match args
+        .iter()
+        .position(|arg| arg == "--input")
+        .and_then(|index| args.get(index + 1))
+    {
+        Some(path) => match ScriptedInput::load(path) {
+            Ok(script) => Box::new(script),
+            Err(error) => {
+                println!("Could not load {}: {}", path, error);
+                return;
+            }
+        },
+        None => Box::new(keyboard),
+    };
+    if args.iter().any(|arg| arg == "--block-opposing") {
+        input = Box::new(BlockOpposing::new(input));
+    }
+    gameboy_cpu.set_input_source(input);
+
+    let stop_frame = args
+        .iter()
+        .position(|arg| arg == "--frames")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|frames| frames.parse::<u32>().ok())
+        .or(record_frames);
+
+    //The last seconds of gameplay are always kept in memory and saved as a GIF by the gif hotkey.  --gif <seconds>
+    //changes how many seconds are kept.  --gif-at <frame> also saves them once that frame is drawn.
+    let mut gif_capture = GifCapture::new(
+        args.iter()
+            .position(|arg| arg == "--gif")
+            .and_then(|index| args.get(index + 1))
+            .and_then(|seconds| seconds.parse::<u32>().ok())
+            .unwrap_or(GIF_SECONDS),
+    );
+    let gif_frame = args
+        .iter()
+        .position(|arg| arg == "--gif-at")
+        .and_then(|index| args.get(index + 1))
+        .and_then(|frame| frame.parse::<u32>().ok());
+
+    //--sprite-limit-report prints the sprites that lost lines to the 10 sprites per line limit each frame
+    let sprite_limit_report = args.iter().any(|arg| arg == "--sprite-limit-report");
+
+    //--blocked-writes-report prints how many vram/oam writes the ppu dropped each frame
+    let blocked_writes_report = args.iter().any(|arg| arg == "--blocked-writes-report");
 
     //load rom into cpu's memory array
     //load_rom(&args[1], &mut gameboy_cpu);
@@ -30,14 +347,193 @@ fn main() {
     //load tetris; hard coded to work with debug
     //load_rom("C:\\Repos\\GBCEmulator\\roms\\Tetris.gb", &mut gameboy_cpu);
 
-    WindowsInterface::load_rom(
-        "C:\\Repos\\GBCEmulator\\roms\\cpu_test\\08-misc instrs.gb",
-        &mut gameboy_cpu,
-    );
+    //--vgm <path> logs the writes to the sound registers so the music can be played back in other tools
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--vgm")
+        .and_then(|index| args.get(index + 1))
+    {
+        if let Err(error) = gameboy_cpu.start_vgm_log(path) {
+            println!("Could not start the VGM log: {}", error);
+        }
+    }
+
+    //--gbs <file> plays a GBS sound file instead of a rom.  --track <n> picks the song, otherwise the file's first song
+    //plays.  --render <path> writes --seconds <n> of the song to a WAV as fast as possible and exits.
+    if let Some(path) = args
+        .iter()
+        .position(|arg| arg == "--gbs")
+        .and_then(|index| args.get(index + 1))
+    {
+        let gbs = match GbsFile::load(path) {
+            Ok(gbs) => gbs,
+            Err(error) => {
+                println!("Could not load {}: {}", path, error);
+                return;
+            }
+        };
+        println!(
+            "{} - {} ({}), {} songs played at {:.1} Hz",
+            gbs.title,
+            gbs.author,
+            gbs.copyright,
+            gbs.song_count,
+            gbs.play_rate()
+        );
 
+        let song = args
+            .iter()
+            .position(|arg| arg == "--track")
+            .and_then(|index| args.get(index + 1))
+            .and_then(|track| track.parse::<u8>().ok())
+            .unwrap_or(gbs.first_song.max(1));
+        if !(1..=gbs.song_count).contains(&song) {
+            println!("The GBS has songs 1 to {}.", gbs.song_count);
+            return;
+        }
+        gameboy_cpu.start_gbs_song(&gbs, song - 1);
+
+        if let Some(wav_path) = args
+            .iter()
+            .position(|arg| arg == "--render")
+            .and_then(|index| args.get(index + 1))
+        {
+            let seconds = args
+                .iter()
+                .position(|arg| arg == "--seconds")
+                .and_then(|index| args.get(index + 1))
+                .and_then(|seconds| seconds.parse::<u64>().ok())
+                .unwrap_or(RENDER_SECONDS);
+            match render_wav(
+                &mut gameboy_cpu,
+                &unprifxed_instructions,
+                &prifxed_instructions,
+                &mut windows,
+                wav_path,
+                seconds,
+            ) {
+                Ok(()) => println!("Saved {}", wav_path),
+                Err(error) => println!("Could not render {}: {}", wav_path, error),
+            }
+            finish_vgm_log(&mut gameboy_cpu);
+            return;
+        }
+    } else {
+        WindowsInterface::load_rom(
+            "C:\\Repos\\GBCEmulator\\roms\\cpu_test\\08-misc instrs.gb",
+            &mut gameboy_cpu,
+        );
+    }
+
+    let mut frame_number: u32 = 0;
     loop {
         gameboy_cpu.execute_step(&unprifxed_instructions, &prifxed_instructions, &mut windows);
 
+        if gameboy_cpu.frame_ready() {
+            frame_number += 1;
+
+            let audio = gameboy_cpu.take_audio_samples();
+            if let Some(active) = recorder.as_mut() {
+                let pushed = active
+                    .push_frame(&gameboy_cpu.frame(), gameboy_cpu.cycle_count())
+                    .and_then(|_| active.push_audio(&audio));
+                if let Err(error) = pushed {
+                    println!("Recording stopped: {}", error);
+                    recorder = None;
+                }
+            }
+
+            if let Some(video) = raw_video.as_mut() {
+                if let Err(error) = video.push_frame(&gameboy_cpu.frame()) {
+                    println!("Raw video stopped: {}", error);
+                    raw_video = None;
+                }
+            }
+
+            gif_capture.push_frame(&gameboy_cpu.frame(), gameboy_cpu.cycle_count());
+            if gif_frame == Some(frame_number) {
+                match windows.save_gif(&gif_capture) {
+                    Ok(path) => println!("Saved {}", path),
+                    Err(error) => println!("Could not save the gif: {}", error),
+                }
+            }
+
+            if screenshot_frame == Some(frame_number) {
+                match windows.save_screenshot(&gameboy_cpu) {
+                    Ok(path) => println!("Saved {}", path),
+                    Err(error) => println!("Could not save the screenshot: {}", error),
+                }
+            }
+
+            if let Some(path) = dump_tiles {
+                save_dump(&gameboy_cpu.tile_sheet(dmg_palette), path, frame_number);
+            }
+            if let Some(path) = dump_map {
+                save_dump(&gameboy_cpu.tile_map(map_address, dmg_palette), path, frame_number);
+            }
+            if let Some(path) = dump_sprites {
+                save_dump(&gameboy_cpu.sprite_sheet(dmg_palette), path, frame_number);
+            }
+
+            if sprite_limit_report {
+                for sprite in gameboy_cpu.oam_info().iter().filter(|sprite| sprite.lines_dropped > 0) {
+                    println!(
+                        "frame {}: sprite {} (x {}, y {}, tile {:#04x}, flags {:#04x}, palette {}) drawn on {} line(s), \
+                         dropped on {}",
+                        frame_number,
+                        sprite.index,
+                        sprite.x,
+                        sprite.y,
+                        sprite.tile,
+                        sprite.flags,
+                        sprite.palette,
+                        sprite.lines_visible,
+                        sprite.lines_dropped
+                    );
+                }
+            }
+
+            //report writes the rom made to vram/oam while the ppu was using them
+            let blocked_writes = gameboy_cpu.take_blocked_vram_writes();
+            if blocked_writes_report && blocked_writes > 0 {
+                println!(
+                    "frame {}: {} vram/oam write(s) dropped by the ppu",
+                    frame_number, blocked_writes
+                );
+            }
+
+            if stop_frame == Some(frame_number) {
+                break;
+            }
+        }
+
+        //The hotkeys are handled whenever the buttons are read, so they keep working while the game has the lcd off
+        if gameboy_cpu.input_polled() {
+            let hotkeys = key_events.take_hotkeys();
+
+            if hotkeys.contains(&Hotkey::SaveGif) {
+                match windows.save_gif(&gif_capture) {
+                    Ok(path) => println!("Saved {}", path),
+                    Err(error) => println!("Could not save the gif: {}", error),
+                }
+            }
+
+            if hotkeys.contains(&Hotkey::Screenshot) {
+                match windows.save_screenshot(&gameboy_cpu) {
+                    Ok(path) => println!("Saved {}", path),
+                    Err(error) => println!("Could not save the screenshot: {}", error),
+                }
+            }
+
+            for hotkey in hotkeys {
+                if let Hotkey::ToggleMute(channel) = hotkey {
+                    let muted = !gameboy_cpu.channel_muted(channel);
+                    gameboy_cpu.set_channel_muted(channel, muted);
+                    println!("Channel {} {}", channel, if muted { "muted" } else { "unmuted" });
+                }
+            }
+        }
+
         //print anything from the serial port
         if gameboy_cpu.read_memory(0xff02) > 0 {
             let mut buff = [0; 4];
@@ -50,5 +546,56 @@ fn main() {
         WindowsInterface::sleep();
     }
 
+    if let Some(active) = recorder.take() {
+        if let Err(error) = active.finish() {
+            println!("Could not finish the recording: {}", error);
+        }
+    }
+    if let Some(video) = raw_video.take() {
+        if let Err(error) = video.finish() {
+            println!("Could not finish the raw video: {}", error);
+        }
+    }
+    if let Err(error) = gameboy_cpu.finish_audio_sink() {
+        println!("Could not finish the audio output: {}", error);
+    }
+    if let Err(error) = gameboy_cpu.finish_channel_sinks() {
+        println!("Could not finish the channel capture: {}", error);
+    }
+    finish_vgm_log(&mut gameboy_cpu);
+
     //    println!("End of Program");
 }
+
+//Runs the emulator as fast as possible for a number of seconds and writes the sound to a WAV
+fn render_wav(
+    cpu: &mut Cpu,
+    unprefixed_instructions: &OpcodeTable,
+    prefixed_instructions: &OpcodeTable,
+    windows: &mut WindowsInterface,
+    path: &str,
+    seconds: u64,
+) -> io::Result<()> {
+    cpu.set_audio_sink(Box::new(WavSink::create(path, cpu.sample_rate())?));
+
+    let end = cpu.cycle_count() + seconds * CPU_CLOCK_HZ;
+    while cpu.cycle_count() < end {
+        cpu.execute_step(unprefixed_instructions, prefixed_instructions, windows);
+    }
+
+    cpu.finish_audio_sink()
+}
+
+fn finish_vgm_log(cpu: &mut Cpu) {
+    if let Err(error) = cpu.stop_vgm_log() {
+        println!("Could not finish the VGM log: {}", error);
+    }
+}
+
+//Writes a debug image.  {frame} in the path is replaced with the frame number.
+fn save_dump(image: &Image, path: &str, frame_number: u32) {
+    let path = path.replace("{frame}", &frame_number.to_string());
+    if let Err(error) = image.save_png(&path) {
+        println!("Could not write {}: {}", path, error);
+    }
+}
@@ -1,5 +1,11 @@
 use crate::rom::*;
 
+pub const BANK_SIZE: usize = ROM_BANK_00_END - ROM_BANK_00_START + 1;
+
+//Writes here select the rom bank mapped at 0x4000-0x7fff
+const BANK_SELECT_START: usize = 0x2000;
+const BANK_SELECT_END: usize = 0x3fff;
+
 pub struct Mcb {
     //16 is not the final size of this array.  I need to be able to buffer a whole rom cart (so atleast 128, but really 512 for mbc 5)
     //Problem is that the stack overflows (on windows) with such a big array.  I could use a vector (moving the data to the heap) but I'm not sure
@@ -9,6 +15,7 @@ pub struct Mcb {
     //bank: [Rom; 16],
     bank: Vec<Rom>,
     current_bank: usize,
+    bank_select: bool, //Only rom images loaded whole can switch banks for now
 }
 
 impl Mcb {
@@ -17,6 +24,7 @@ impl Mcb {
             //bank: [Rom::new(); 16],
             bank: vec![Rom::new(), Rom::new()], //init two banks
             current_bank: 1,
+            bank_select: false,
         }
     }
 
@@ -36,7 +44,33 @@ impl Mcb {
         self.bank[self.current_bank].write_memory(index - ROM_BANK_01_START, data);
     }
 
-    pub fn change_bank(&self) {
+    //Loads a whole rom image, split in as many banks as it needs
+    pub fn load_image(&mut self, image: &[u8]) {
+        self.bank = image
+            .chunks(BANK_SIZE)
+            .map(|chunk| {
+                let mut rom = Rom::new();
+                for (i, data) in chunk.iter().enumerate() {
+                    rom.write_memory(i, *data);
+                }
+                rom
+            })
+            .collect();
+        while self.bank.len() < 2 {
+            self.bank.push(Rom::new());
+        }
+
+        self.current_bank = 1;
+        self.bank_select = true;
+    }
+
+    pub fn change_bank(&mut self, index: usize, data: u8) {
+        //Bank 0 can't be mapped twice, selecting it maps bank 1
+        if self.bank_select && (BANK_SELECT_START..=BANK_SELECT_END).contains(&index) {
+            self.current_bank = (data as usize).max(1) % self.bank.len();
+            return;
+        }
+
         //stub -- this code will update current_bank
         println!("Change_bank not implemented");
     }
@@ -14,9 +14,9 @@ impl OpcodeTable {
                 Opcode::new(0x03, "INC BC".to_string(), 2, 1, Opcode::inc_rr),
                 Opcode::new(0x04, "INC B".to_string(), 1, 1, Opcode::inc_r),
                 Opcode::new(0x05, "DEC B".to_string(), 1, 1, Opcode::dec_r),
-                Opcode::new(0x06, "LD B, u8".to_string(), 1, 2, Opcode::load_r_n),
-                Opcode::new(0x07, "RLCA".to_string(), 2, 1, Opcode::rlca),
-                Opcode::new(0x08, "LD (u16), SP".to_string(), 2, 3, Opcode::load_nn_sp),
+                Opcode::new(0x06, "LD B, u8".to_string(), 2, 2, Opcode::load_r_n),
+                Opcode::new(0x07, "RLCA".to_string(), 1, 1, Opcode::rlca),
+                Opcode::new(0x08, "LD (u16), SP".to_string(), 5, 3, Opcode::load_nn_sp),
                 Opcode::new(0x09, "ADD HL, BC".to_string(), 2, 1, Opcode::add_hl_rr),
                 Opcode::new(0x0A, "LD A, (BC)".to_string(), 2, 1, Opcode::load_a_bc),
                 Opcode::new(0x0B, "DEC BC".to_string(), 2, 1, Opcode::dec_rr),
@@ -30,7 +30,7 @@ impl OpcodeTable {
                 Opcode::new(0x13, "INC DE".to_string(), 2, 1, Opcode::inc_rr),
                 Opcode::new(0x14, "INC D".to_string(), 1, 1, Opcode::inc_r),
                 Opcode::new(0x15, "DEC D".to_string(), 1, 1, Opcode::dec_r),
-                Opcode::new(0x16, "LD D, u8".to_string(), 1, 2, Opcode::load_r_n),
+                Opcode::new(0x16, "LD D, u8".to_string(), 2, 2, Opcode::load_r_n),
                 Opcode::new(0x17, "RLA".to_string(), 1, 1, Opcode::rla),
                 Opcode::new(0x18, "JR i8".to_string(), 3, 2, Opcode::jr_dd),
                 Opcode::new(0x19, "ADD HL, DE".to_string(), 2, 1, Opcode::add_hl_rr),
@@ -38,9 +38,9 @@ impl OpcodeTable {
                 Opcode::new(0x1B, "DEC DE".to_string(), 2, 1, Opcode::dec_rr),
                 Opcode::new(0x1C, "INC E".to_string(), 1, 1, Opcode::inc_r),
                 Opcode::new(0x1D, "DEC D".to_string(), 1, 1, Opcode::dec_r),
-                Opcode::new(0x1E, "LD E, u8".to_string(), 4, 2, Opcode::load_r_n),
+                Opcode::new(0x1E, "LD E, u8".to_string(), 2, 2, Opcode::load_r_n),
                 Opcode::new(0x1F, "RRA".to_string(), 1, 1, Opcode::rra),
-                Opcode::new(0x20, "JR NZ, i8".to_string(), 3, 2, Opcode::jr_conditional),
+                Opcode::new(0x20, "JR NZ, i8".to_string(), 2, 2, Opcode::jr_conditional),
                 Opcode::new(0x21, "LD HL, u16".to_string(), 3, 3, Opcode::load_hl_nn),
                 Opcode::new(0x22, "LD (HL+), A".to_string(), 2, 1, Opcode::load_hl_a_inc),
                 Opcode::new(0x23, "INC HL".to_string(), 2, 1, Opcode::inc_rr),
@@ -48,25 +48,25 @@ impl OpcodeTable {
                 Opcode::new(0x25, "DEC H".to_string(), 1, 1, Opcode::dec_r),
                 Opcode::new(0x26, "LD H, u8".to_string(), 2, 2, Opcode::load_r_n),
                 Opcode::new(0x27, "DAA".to_string(), 1, 1, Opcode::daa),
-                Opcode::new(0x28, "JR Z, i8".to_string(), 3, 2, Opcode::jr_conditional),
-                Opcode::new(0x29, "ADD HL, HL".to_string(), 1, 1, Opcode::add_hl_rr),
+                Opcode::new(0x28, "JR Z, i8".to_string(), 2, 2, Opcode::jr_conditional),
+                Opcode::new(0x29, "ADD HL, HL".to_string(), 2, 1, Opcode::add_hl_rr),
                 Opcode::new(0x2A, "LD A, (HL+)".to_string(), 2, 1, Opcode::load_a_hl_inc),
                 Opcode::new(0x2B, "DEC HL".to_string(), 2, 1, Opcode::dec_rr),
                 Opcode::new(0x2C, "INC L".to_string(), 1, 1, Opcode::inc_r),
                 Opcode::new(0x2D, "DEC L".to_string(), 1, 1, Opcode::dec_r),
                 Opcode::new(0x2E, "LD L, u8".to_string(), 2, 2, Opcode::load_r_n),
                 Opcode::new(0x2F, "CPL".to_string(), 1, 1, Opcode::cpl),
-                Opcode::new(0x30, "JR NC, i8".to_string(), 3, 2, Opcode::jr_conditional),
+                Opcode::new(0x30, "JR NC, i8".to_string(), 2, 2, Opcode::jr_conditional),
                 Opcode::new(0x31, "LD SP, u16".to_string(), 3, 3, Opcode::load_sp_nn),
-                Opcode::new(0x32, "LD (HL-), A".to_string(), 1, 1, Opcode::load_hl_a_dec),
+                Opcode::new(0x32, "LD (HL-), A".to_string(), 2, 1, Opcode::load_hl_a_dec),
                 Opcode::new(0x33, "INC SP".to_string(), 2, 1, Opcode::inc_sp),
                 Opcode::new(0x34, "INC (HL)".to_string(), 3, 1, Opcode::inc_hl),
                 Opcode::new(0x35, "DEC (HL)".to_string(), 3, 1, Opcode::dec_hl),
                 Opcode::new(0x36, "LD (HL), u8".to_string(), 3, 2, Opcode::load_hl_n),
                 Opcode::new(0x37, "SCF".to_string(), 1, 1, Opcode::scf),
-                Opcode::new(0x38, "JR C, i8".to_string(), 1, 2, Opcode::jr_conditional),
+                Opcode::new(0x38, "JR C, i8".to_string(), 2, 2, Opcode::jr_conditional),
                 Opcode::new(0x39, "ADD HL, SP".to_string(), 2, 1, Opcode::add_hl_sp),
-                Opcode::new(0x3A, "LD A, (HL-)".to_string(), 1, 1, Opcode::load_a_hl_dec),
+                Opcode::new(0x3A, "LD A, (HL-)".to_string(), 2, 1, Opcode::load_a_hl_dec),
                 Opcode::new(0x3B, "DEC SP".to_string(), 2, 1, Opcode::dec_sp),
                 Opcode::new(0x3C, "INC A".to_string(), 1, 1, Opcode::inc_r),
                 Opcode::new(0x3D, "DEC a".to_string(), 1, 1, Opcode::dec_r),
@@ -86,7 +86,7 @@ impl OpcodeTable {
                 Opcode::new(0x4B, "LD C, E".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x4C, "LD C, H".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x4D, "LD C, L".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x4E, "LD C, (HL)".to_string(), 1, 1, Opcode::load_r_hl),
+                Opcode::new(0x4E, "LD C, (HL)".to_string(), 2, 1, Opcode::load_r_hl),
                 Opcode::new(0x4F, "LD C, A".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x50, "LD D, B".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x51, "LD D, C".to_string(), 1, 1, Opcode::load_r_r),
@@ -94,7 +94,7 @@ impl OpcodeTable {
                 Opcode::new(0x53, "LD D, E".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x54, "LD D, H".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x55, "LD D, L".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x56, "LD D, (HL)".to_string(), 1, 1, Opcode::load_r_hl),
+                Opcode::new(0x56, "LD D, (HL)".to_string(), 2, 1, Opcode::load_r_hl),
                 Opcode::new(0x57, "LD D, A".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x58, "LD E, B".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x59, "LD E, C".to_string(), 1, 1, Opcode::load_r_r),
@@ -102,7 +102,7 @@ impl OpcodeTable {
                 Opcode::new(0x5B, "LD E, E".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x5C, "LD E, H".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x5D, "LD E, L".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x5E, "LD E, HL".to_string(), 1, 1, Opcode::load_r_hl),
+                Opcode::new(0x5E, "LD E, HL".to_string(), 2, 1, Opcode::load_r_hl),
                 Opcode::new(0x5F, "LD E, A".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x60, "LD H, B".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x61, "LD H, C".to_string(), 1, 1, Opcode::load_r_r),
@@ -110,7 +110,7 @@ impl OpcodeTable {
                 Opcode::new(0x63, "LD H, E".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x64, "LD H, H".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x65, "LD H, L".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x66, "LD H, HL".to_string(), 1, 1, Opcode::load_r_hl),
+                Opcode::new(0x66, "LD H, HL".to_string(), 2, 1, Opcode::load_r_hl),
                 Opcode::new(0x67, "LD H, A".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x68, "LD L, B".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x69, "LD L, C".to_string(), 1, 1, Opcode::load_r_r),
@@ -118,23 +118,23 @@ impl OpcodeTable {
                 Opcode::new(0x6B, "LD L, E".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x6C, "LD L, H".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x6D, "LD L, L".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x6E, "LD L, HL".to_string(), 1, 1, Opcode::load_r_hl),
+                Opcode::new(0x6E, "LD L, HL".to_string(), 2, 1, Opcode::load_r_hl),
                 Opcode::new(0x6F, "LD L, A".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x70, "LD HL, B".to_string(), 1, 1, Opcode::load_hl_r),
-                Opcode::new(0x71, "LD HL, C".to_string(), 1, 1, Opcode::load_hl_r),
-                Opcode::new(0x72, "LD HL, D".to_string(), 1, 1, Opcode::load_hl_r),
-                Opcode::new(0x73, "LD HL, E".to_string(), 1, 1, Opcode::load_hl_r),
-                Opcode::new(0x74, "LD HL, H".to_string(), 1, 1, Opcode::load_hl_r),
-                Opcode::new(0x75, "LD HL, L".to_string(), 1, 1, Opcode::load_hl_r),
+                Opcode::new(0x70, "LD HL, B".to_string(), 2, 1, Opcode::load_hl_r),
+                Opcode::new(0x71, "LD HL, C".to_string(), 2, 1, Opcode::load_hl_r),
+                Opcode::new(0x72, "LD HL, D".to_string(), 2, 1, Opcode::load_hl_r),
+                Opcode::new(0x73, "LD HL, E".to_string(), 2, 1, Opcode::load_hl_r),
+                Opcode::new(0x74, "LD HL, H".to_string(), 2, 1, Opcode::load_hl_r),
+                Opcode::new(0x75, "LD HL, L".to_string(), 2, 1, Opcode::load_hl_r),
                 Opcode::new(0x76, "HALT".to_string(), 1, 1, Opcode::halt),
-                Opcode::new(0x77, "LD HL, A".to_string(), 1, 1, Opcode::load_hl_r),
+                Opcode::new(0x77, "LD HL, A".to_string(), 2, 1, Opcode::load_hl_r),
                 Opcode::new(0x78, "LD A, B".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x79, "LD A, C".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x7A, "LD A, D".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x7B, "LD A, E".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x7C, "LD A, H".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x7D, "LD A, L".to_string(), 1, 1, Opcode::load_r_r),
-                Opcode::new(0x7E, "LD A, (HL)".to_string(), 1, 1, Opcode::load_r_hl),
+                Opcode::new(0x7E, "LD A, (HL)".to_string(), 2, 1, Opcode::load_r_hl),
                 Opcode::new(0x7F, "LD A, A".to_string(), 1, 1, Opcode::load_r_r),
                 Opcode::new(0x80, "ADD A, B".to_string(), 1, 1, Opcode::add_a_r),
                 Opcode::new(0x81, "ADD A, C".to_string(), 1, 1, Opcode::add_a_r),
@@ -142,7 +142,7 @@ impl OpcodeTable {
                 Opcode::new(0x83, "ADD A, E".to_string(), 1, 1, Opcode::add_a_r),
                 Opcode::new(0x84, "ADD A, H".to_string(), 1, 1, Opcode::add_a_r),
                 Opcode::new(0x85, "ADD A, L".to_string(), 1, 1, Opcode::add_a_r),
-                Opcode::new(0x86, "ADD A, (HL)".to_string(), 1, 1, Opcode::add_a_hl),
+                Opcode::new(0x86, "ADD A, (HL)".to_string(), 2, 1, Opcode::add_a_hl),
                 Opcode::new(0x87, "ADD A, A".to_string(), 1, 1, Opcode::add_a_r),
                 Opcode::new(0x88, "ADC A, B".to_string(), 1, 1, Opcode::adc_a_r),
                 Opcode::new(0x89, "ADC A, C".to_string(), 1, 1, Opcode::adc_a_r),
@@ -150,7 +150,7 @@ impl OpcodeTable {
                 Opcode::new(0x8B, "ADC A, E".to_string(), 1, 1, Opcode::adc_a_r),
                 Opcode::new(0x8C, "ADC A, H".to_string(), 1, 1, Opcode::adc_a_r),
                 Opcode::new(0x8D, "ADC A, L".to_string(), 1, 1, Opcode::adc_a_r),
-                Opcode::new(0x8E, "ADC A, (HL)".to_string(), 1, 1, Opcode::adc_a_hl),
+                Opcode::new(0x8E, "ADC A, (HL)".to_string(), 2, 1, Opcode::adc_a_hl),
                 Opcode::new(0x8F, "ADC A, A".to_string(), 1, 1, Opcode::adc_a_r),
                 Opcode::new(0x90, "SUB A, B".to_string(), 1, 1, Opcode::sub_a_r),
                 Opcode::new(0x91, "SUB A, C".to_string(), 1, 1, Opcode::sub_a_r),
@@ -158,7 +158,7 @@ impl OpcodeTable {
                 Opcode::new(0x93, "SUB A, E".to_string(), 1, 1, Opcode::sub_a_r),
                 Opcode::new(0x94, "SUB A, H".to_string(), 1, 1, Opcode::sub_a_r),
                 Opcode::new(0x95, "SUB A, L".to_string(), 1, 1, Opcode::sub_a_r),
-                Opcode::new(0x96, "SUB A, (HL)".to_string(), 1, 1, Opcode::sub_a_hl),
+                Opcode::new(0x96, "SUB A, (HL)".to_string(), 2, 1, Opcode::sub_a_hl),
                 Opcode::new(0x97, "SUB A, A".to_string(), 1, 1, Opcode::sub_a_r),
                 Opcode::new(0x98, "SBC A, B".to_string(), 1, 1, Opcode::sbc_a_r),
                 Opcode::new(0x99, "SBC A, C".to_string(), 1, 1, Opcode::sbc_a_r),
@@ -166,7 +166,7 @@ impl OpcodeTable {
                 Opcode::new(0x9B, "SBC A, E".to_string(), 1, 1, Opcode::sbc_a_r),
                 Opcode::new(0x9C, "SBC A, H".to_string(), 1, 1, Opcode::sbc_a_r),
                 Opcode::new(0x9D, "SBC A, L".to_string(), 1, 1, Opcode::sbc_a_r),
-                Opcode::new(0x9E, "SBC A, (HL)".to_string(), 1, 1, Opcode::sbc_a_hl),
+                Opcode::new(0x9E, "SBC A, (HL)".to_string(), 2, 1, Opcode::sbc_a_hl),
                 Opcode::new(0x9F, "SBC A, A".to_string(), 1, 1, Opcode::sbc_a_r),
                 Opcode::new(0xA0, "AND A, B".to_string(), 1, 1, Opcode::and_a_r),
                 Opcode::new(0xA1, "AND A, C".to_string(), 1, 1, Opcode::and_a_r),
@@ -174,7 +174,7 @@ impl OpcodeTable {
                 Opcode::new(0xA3, "AND A, E".to_string(), 1, 1, Opcode::and_a_r),
                 Opcode::new(0xA4, "AND A, H".to_string(), 1, 1, Opcode::and_a_r),
                 Opcode::new(0xA5, "AND A, L".to_string(), 1, 1, Opcode::and_a_r),
-                Opcode::new(0xA6, "AND A, (HL)".to_string(), 1, 1, Opcode::and_a_hl),
+                Opcode::new(0xA6, "AND A, (HL)".to_string(), 2, 1, Opcode::and_a_hl),
                 Opcode::new(0xA7, "AND A, A".to_string(), 1, 1, Opcode::and_a_r),
                 Opcode::new(0xA8, "XOR A, B".to_string(), 1, 1, Opcode::xor_a_r),
                 Opcode::new(0xA9, "XOR A, C".to_string(), 1, 1, Opcode::xor_a_r),
@@ -182,7 +182,7 @@ impl OpcodeTable {
                 Opcode::new(0xAB, "XOR A, E".to_string(), 1, 1, Opcode::xor_a_r),
                 Opcode::new(0xAC, "XOR A, H".to_string(), 1, 1, Opcode::xor_a_r),
                 Opcode::new(0xAD, "XOR A, L".to_string(), 1, 1, Opcode::xor_a_r),
-                Opcode::new(0xAE, "XOR A, (HL)".to_string(), 1, 1, Opcode::xor_a_hl),
+                Opcode::new(0xAE, "XOR A, (HL)".to_string(), 2, 1, Opcode::xor_a_hl),
                 Opcode::new(0xAF, "XOR A, A".to_string(), 1, 1, Opcode::xor_a_r),
                 Opcode::new(0xB0, "OR A, B".to_string(), 1, 1, Opcode::or_a_r),
                 Opcode::new(0xB1, "OR A, C".to_string(), 1, 1, Opcode::or_a_r),
@@ -190,7 +190,7 @@ impl OpcodeTable {
                 Opcode::new(0xB3, "OR A, E".to_string(), 1, 1, Opcode::or_a_r),
                 Opcode::new(0xB4, "OR A, H".to_string(), 1, 1, Opcode::or_a_r),
                 Opcode::new(0xB5, "OR A, L".to_string(), 1, 1, Opcode::or_a_r),
-                Opcode::new(0xB6, "OR A, (HL)".to_string(), 1, 1, Opcode::or_a_hl),
+                Opcode::new(0xB6, "OR A, (HL)".to_string(), 2, 1, Opcode::or_a_hl),
                 Opcode::new(0xB7, "OR A, A".to_string(), 1, 1, Opcode::or_a_r),
                 Opcode::new(0xB8, "CP A, B".to_string(), 1, 1, Opcode::cp_a_r),
                 Opcode::new(0xB9, "CP A, C".to_string(), 1, 1, Opcode::cp_a_r),
@@ -198,21 +198,21 @@ impl OpcodeTable {
                 Opcode::new(0xBB, "CP A, E".to_string(), 1, 1, Opcode::cp_a_r),
                 Opcode::new(0xBC, "CP A, H".to_string(), 1, 1, Opcode::cp_a_r),
                 Opcode::new(0xBD, "CP A, L".to_string(), 1, 1, Opcode::cp_a_r),
-                Opcode::new(0xBE, "CP A, (HL)".to_string(), 1, 1, Opcode::cp_a_hl),
+                Opcode::new(0xBE, "CP A, (HL)".to_string(), 2, 1, Opcode::cp_a_hl),
                 Opcode::new(0xBF, "CP A, A".to_string(), 1, 1, Opcode::cp_a_r),
                 Opcode::new(0xC0, "RET NZ".to_string(), 2, 1, Opcode::ret_conditional),
                 Opcode::new(0xC1, "POP BC".to_string(), 3, 1, Opcode::pop_bc),
                 Opcode::new(0xC2, "JP NZ, u16".to_string(), 3, 3, Opcode::jp_conditional),
                 Opcode::new(0xC3, "JP u16".to_string(), 4, 3, Opcode::jp_nn),
-                Opcode::new(0xC4, "CALL NZ, u16".to_string(), 1, 3, Opcode::call_conditional),
+                Opcode::new(0xC4, "CALL NZ, u16".to_string(), 3, 3, Opcode::call_conditional),
                 Opcode::new(0xC5, "PUSH BC".to_string(), 4, 1, Opcode::push_bc),
-                Opcode::new(0xC6, "ADD A, u8".to_string(), 1, 2, Opcode::add_a_n),
+                Opcode::new(0xC6, "ADD A, u8".to_string(), 2, 2, Opcode::add_a_n),
                 Opcode::new(0xC7, "RST 00h".to_string(), 4, 1, Opcode::rst),
                 Opcode::new(0xC8, "RET Z".to_string(), 2, 1, Opcode::ret_conditional),
                 Opcode::new(0xC9, "RET".to_string(), 4, 1, Opcode::ret),
                 Opcode::new(0xCA, "JP Z, u16".to_string(), 3, 3, Opcode::jp_conditional),
                 //CB Is a prefix for the second table of 256 instructions.  It should never be executed from this table
-                Opcode::new(0xCB, "PREFIX CB".to_string(), 3, 2, Opcode::nop),
+                Opcode::new(0xCB, "PREFIX CB".to_string(), 1, 2, Opcode::nop),
                 Opcode::new(0xCC, "CALL Z, u16".to_string(), 3, 3, Opcode::call_conditional),
                 Opcode::new(0xCD, "CALL u16".to_string(), 6, 3, Opcode::call_nn),
                 Opcode::new(0xCE, "ADC A, u8".to_string(), 2, 2, Opcode::adc_a_n),
@@ -225,17 +225,17 @@ impl OpcodeTable {
                 Opcode::new(0xD5, "PUSH DE".to_string(), 4, 1, Opcode::push_de),
                 Opcode::new(0xD6, "SUB A, u8".to_string(), 2, 2, Opcode::sub_a_n),
                 Opcode::new(0xD7, "RST 10h".to_string(), 4, 1, Opcode::rst),
-                Opcode::new(0xD8, "RET C".to_string(), 4, 1, Opcode::ret_conditional),
+                Opcode::new(0xD8, "RET C".to_string(), 2, 1, Opcode::ret_conditional),
                 Opcode::new(0xD9, "RETI".to_string(), 4, 1, Opcode::reti),
                 Opcode::new(0xDA, "JP C, u16".to_string(), 3, 3, Opcode::jp_conditional),
                 Opcode::new(0xDB, "No Op DB".to_string(), 1, 1, Opcode::nop),
-                Opcode::new(0xDC, "CALL C, u16".to_string(), 1, 3, Opcode::call_conditional),
+                Opcode::new(0xDC, "CALL C, u16".to_string(), 3, 3, Opcode::call_conditional),
                 Opcode::new(0xDD, "No Op DD".to_string(), 1, 1, Opcode::nop),
                 Opcode::new(0xDE, "SBC A, u8".to_string(), 2, 2, Opcode::sbc_a_n),
                 Opcode::new(0xDF, "RST 18h".to_string(), 4, 1, Opcode::rst),
-                Opcode::new(0xE0, "LD (FF00+u8), A".to_string(), 1, 2, Opcode::ldh_n_a),
+                Opcode::new(0xE0, "LD (FF00+u8), A".to_string(), 3, 2, Opcode::ldh_n_a),
                 Opcode::new(0xE1, "POP HL".to_string(), 3, 1, Opcode::pop_hl),
-                Opcode::new(0xE2, "LD (FF00+C), A".to_string(), 1, 1, Opcode::ldh_c_a),
+                Opcode::new(0xE2, "LD (FF00+C), A".to_string(), 2, 1, Opcode::ldh_c_a),
                 Opcode::new(0xE3, "No Op 0xE3".to_string(), 1, 1, Opcode::nop),
                 Opcode::new(0xE4, "No Op 0xE4".to_string(), 1, 1, Opcode::nop),
                 Opcode::new(0xE5, "PUSH HL".to_string(), 4, 1, Opcode::push_hl),
@@ -243,7 +243,7 @@ impl OpcodeTable {
                 Opcode::new(0xE7, "RST 20h".to_string(), 4, 1, Opcode::rst),
                 Opcode::new(0xE8, "ADD SP, i8".to_string(), 4, 2, Opcode::add_sp_dd),
                 Opcode::new(0xE9, "JP HL".to_string(), 1, 1, Opcode::jp_hl),
-                Opcode::new(0xEA, "LD (u16), A".to_string(), 1, 3, Opcode::load_nn_a),
+                Opcode::new(0xEA, "LD (u16), A".to_string(), 4, 3, Opcode::load_nn_a),
                 Opcode::new(0xEB, "No Op 0xEB".to_string(), 1, 1, Opcode::nop),
                 Opcode::new(0xEC, "No Op 0xEC".to_string(), 1, 1, Opcode::nop),
                 Opcode::new(0xED, "No Op 0xED".to_string(), 1, 1, Opcode::nop),
@@ -406,7 +406,7 @@ impl OpcodeTable {
                 Opcode::new(0x83, "RES 0, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x84, "RES 0, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x85, "RES 0, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0x86, "RES 0, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0x86, "RES 0, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0x87, "RES 0, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x88, "RES 1, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x89, "RES 1, C".to_string(), 2, 2, Opcode::res_nr),
@@ -414,7 +414,7 @@ impl OpcodeTable {
                 Opcode::new(0x8B, "RES 1, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x8C, "RES 1, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x8D, "RES 1, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0x8E, "RES 1, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0x8E, "RES 1, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0x8F, "RES 1, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x90, "RES 2, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x91, "RES 2, C".to_string(), 2, 2, Opcode::res_nr),
@@ -422,7 +422,7 @@ impl OpcodeTable {
                 Opcode::new(0x93, "RES 2, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x94, "RES 2, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x95, "RES 2, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0x96, "RES 2, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0x96, "RES 2, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0x97, "RES 2, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x98, "RES 3, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x99, "RES 3, C".to_string(), 2, 2, Opcode::res_nr),
@@ -430,7 +430,7 @@ impl OpcodeTable {
                 Opcode::new(0x9B, "RES 3, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x9C, "RES 3, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0x9D, "RES 3, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0x9E, "RES 3, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0x9E, "RES 3, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0x9F, "RES 3, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xA0, "RES 4, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xA1, "RES 4, C".to_string(), 2, 2, Opcode::res_nr),
@@ -438,7 +438,7 @@ impl OpcodeTable {
                 Opcode::new(0xA3, "RES 4, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xA4, "RES 4, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xA5, "RES 4, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0xA6, "RES 4, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0xA6, "RES 4, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0xA7, "RES 4, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xA8, "RES 5, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xA9, "RES 5, C".to_string(), 2, 2, Opcode::res_nr),
@@ -446,7 +446,7 @@ impl OpcodeTable {
                 Opcode::new(0xAB, "RES 5, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xAC, "RES 5, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xAD, "RES 5, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0xAE, "RES 5, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0xAE, "RES 5, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0xAF, "RES 5, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xB0, "RES 6, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xB1, "RES 6, C".to_string(), 2, 2, Opcode::res_nr),
@@ -454,7 +454,7 @@ impl OpcodeTable {
                 Opcode::new(0xB3, "RES 6, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xB4, "RES 6, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xB5, "RES 6, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0xB6, "RES 6, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0xB6, "RES 6, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0xB7, "RES 6, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xB8, "RES 7, B".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xB9, "RES 7, C".to_string(), 2, 2, Opcode::res_nr),
@@ -462,7 +462,7 @@ impl OpcodeTable {
                 Opcode::new(0xBB, "RES 7, E".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xBC, "RES 7, H".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xBD, "RES 7, L".to_string(), 2, 2, Opcode::res_nr),
-                Opcode::new(0xBE, "RES 7, (HL)".to_string(), 3, 2, Opcode::res_nhl),
+                Opcode::new(0xBE, "RES 7, (HL)".to_string(), 4, 2, Opcode::res_nhl),
                 Opcode::new(0xBF, "RES 7, A".to_string(), 2, 2, Opcode::res_nr),
                 Opcode::new(0xC0, "SET 0, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xC1, "SET 0, C".to_string(), 2, 2, Opcode::set_nr),
@@ -470,7 +470,7 @@ impl OpcodeTable {
                 Opcode::new(0xC3, "SET 0, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xC4, "SET 0, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xC5, "SET 0, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xC6, "SET 0, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xC6, "SET 0, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xC7, "SET 0, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xC8, "SET 1, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xC9, "SET 1, C".to_string(), 2, 2, Opcode::set_nr),
@@ -478,7 +478,7 @@ impl OpcodeTable {
                 Opcode::new(0xCB, "SET 1, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xCC, "SET 1, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xCD, "SET 1, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xCE, "SET 1, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xCE, "SET 1, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xCF, "SET 1, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xD0, "SET 2, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xD1, "SET 2, C".to_string(), 2, 2, Opcode::set_nr),
@@ -486,7 +486,7 @@ impl OpcodeTable {
                 Opcode::new(0xD3, "SET 2, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xD4, "SET 2, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xD5, "SET 2, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xD6, "SET 2, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xD6, "SET 2, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xD7, "SET 2, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xD8, "SET 3, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xD9, "SET 3, C".to_string(), 2, 2, Opcode::set_nr),
@@ -494,7 +494,7 @@ impl OpcodeTable {
                 Opcode::new(0xDB, "SET 3, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xDC, "SET 3, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xDD, "SET 3, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xDE, "SET 3, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xDE, "SET 3, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xDF, "SET 3, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xE0, "SET 4, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xE1, "SET 4, C".to_string(), 2, 2, Opcode::set_nr),
@@ -502,7 +502,7 @@ impl OpcodeTable {
                 Opcode::new(0xE3, "SET 4, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xE4, "SET 4, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xE5, "SET 4, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xE6, "SET 4, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xE6, "SET 4, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xE7, "SET 4, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xE8, "SET 5, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xE9, "SET 5, C".to_string(), 2, 2, Opcode::set_nr),
@@ -510,7 +510,7 @@ impl OpcodeTable {
                 Opcode::new(0xEB, "SET 5, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xEC, "SET 5, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xED, "SET 5, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xEE, "SET 5, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xEE, "SET 5, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xEF, "SET 5, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xF0, "SET 6, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xF1, "SET 6, C".to_string(), 2, 2, Opcode::set_nr),
@@ -518,7 +518,7 @@ impl OpcodeTable {
                 Opcode::new(0xF3, "SET 6, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xF4, "SET 6, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xF5, "SET 6, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xF6, "SET 6, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xF6, "SET 6, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xF7, "SET 6, A".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xF8, "SET 7, B".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xF9, "SET 7, C".to_string(), 2, 2, Opcode::set_nr),
@@ -526,7 +526,7 @@ impl OpcodeTable {
                 Opcode::new(0xFB, "SET 7, E".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xFC, "SET 7, H".to_string(), 2, 2, Opcode::set_nr),
                 Opcode::new(0xFD, "SET 7, L".to_string(), 2, 2, Opcode::set_nr),
-                Opcode::new(0xFE, "SET 7, (HL)".to_string(), 3, 2, Opcode::set_nhl),
+                Opcode::new(0xFE, "SET 7, (HL)".to_string(), 4, 2, Opcode::set_nhl),
                 Opcode::new(0xFF, "SET 7, A".to_string(), 2, 2, Opcode::set_nr),
             ],
         }
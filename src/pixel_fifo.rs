@@ -0,0 +1,288 @@
+use crate::ppu::*;
+use crate::vram::{decode_tile_line, TilePixelLine};
+
+//Pixel fifo implementation of mode 3: https://gbdev.io/pandocs/pixel_fifo.html
+//The background fetcher fills the background fifo 8 pixels at a time and one pixel is shifted out to the lcd each dot.
+//Sprites stop the pixel output while their data is fetched and mixed into the object fifo.
+
+const FIFO_SIZE: usize = 8;
+const DOTS_PER_FETCH_STEP: u8 = 2;
+const STARTUP_DOTS: u8 = 6; //The first tile fetch of every line is thrown away
+const LOW_PLANE: usize = 0;
+const HIGH_PLANE: usize = 1;
+
+#[derive(Copy, Clone)]
+pub struct FifoPixel {
+    pub color: u8, //2-bit color index
+    pub flags: u8, //sprite attributes, or the map attributes of background pixels in CGB mode
+    pub sprite: bool,
+    pub oam_index: u8,
+}
+
+impl FifoPixel {
+    pub fn new() -> Self {
+        Self {
+            color: 0,
+            flags: 0,
+            sprite: false,
+            oam_index: 0,
+        }
+    }
+
+    pub fn sprite(color: u8, flags: u8, oam_index: u8) -> Self {
+        Self {
+            color,
+            flags,
+            sprite: true,
+            oam_index,
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum FetcherStep {
+    Tile,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+pub struct PixelFifo {
+    bg_fifo: [FifoPixel; FIFO_SIZE],
+    bg_index: usize,
+    bg_len: usize,
+    obj_fifo: [FifoPixel; FIFO_SIZE],
+    fetcher_step: FetcherStep,
+    fetcher_dots: u8,
+    fetch_column: u8, //Tile column the fetcher is on, relative to the start of the line or window
+    tile: u8,
+    tile_attributes: u8,
+    tile_line: u8,
+    tile_low: u8, //Low bitplane latched by the DataLow step
+    tile_pixels: TilePixelLine,
+    lcd_x: u8,   //Number of pixels sent to the lcd
+    discard: u8, //Pixels left to throw away for SCX fine scrolling
+    startup_dots: u8,
+    window_active: bool,
+    sprites_fetched: [bool; MAX_SPRITES_PER_LINE], //Indexed by position in the line's sprite list
+    sprite_fetch: Option<usize>,
+    sprite_dots: u8,
+}
+
+impl PixelFifo {
+    pub fn new() -> Self {
+        Self {
+            bg_fifo: [FifoPixel::new(); FIFO_SIZE],
+            bg_index: 0,
+            bg_len: 0,
+            obj_fifo: [FifoPixel::new(); FIFO_SIZE],
+            fetcher_step: FetcherStep::Tile,
+            fetcher_dots: 0,
+            fetch_column: 0,
+            tile: 0,
+            tile_attributes: 0,
+            tile_line: 0,
+            tile_low: 0,
+            tile_pixels: [0; 8],
+            lcd_x: 0,
+            discard: 0,
+            startup_dots: 0,
+            window_active: false,
+            sprites_fetched: [false; MAX_SPRITES_PER_LINE],
+            sprite_fetch: None,
+            sprite_dots: 0,
+        }
+    }
+
+    //Resets the fetchers at the start of mode 3
+    pub fn start_line(&mut self, scx: u8) {
+        *self = Self::new();
+        self.discard = scx % 8;
+        self.startup_dots = STARTUP_DOTS;
+    }
+
+    pub fn window_active(&self) -> bool {
+        self.window_active
+    }
+
+    //Runs the fifo for one dot.  Returns the pixel shifted out to the lcd, if any.
+    pub fn step(&mut self, bus: &PpuBus) -> Option<FifoPixel> {
+        if self.startup_dots > 0 {
+            self.startup_dots -= 1;
+            return None;
+        }
+
+        let lcdc = bus.register(LCD_CRTL_REG);
+
+        //Reaching WX restarts the fetcher on the window tile map
+        if !self.window_active
+            && self.discard == 0
+            && lcdc & LCDC_WINDOW_ENABLE > 0
+            && bus.window_triggered
+            && self.lcd_x as u16 + 7 >= bus.register(LCD_WX_REG) as u16
+        {
+            self.window_active = true;
+            self.bg_len = 0;
+            self.fetch_column = 0;
+            self.fetcher_step = FetcherStep::Tile;
+            self.fetcher_dots = 0;
+        }
+
+        //Sprites are checked once the next pixel is ready to be shifted out
+        let pixel_ready = self.bg_len > 0 || self.fetcher_step == FetcherStep::Push;
+        if self.sprite_fetch.is_none() && pixel_ready && self.discard == 0 && lcdc & LCDC_OBJ_ENABLE > 0 {
+            self.sprite_fetch = self.next_sprite(bus);
+            if let Some(sprite_index) = self.sprite_fetch {
+                let sprite_x = bus.sprite_x(bus.line_sprites[sprite_index] as usize);
+                self.sprite_dots = sprite_penalty(sprite_x, bus.register(LCD_SCX_REG)) as u8;
+            }
+        }
+
+        //Pixels stop shifting out while a sprite is fetched.  The background fetcher keeps going until its tile is ready.
+        if let Some(sprite_index) = self.sprite_fetch {
+            if self.fetcher_step != FetcherStep::Push {
+                self.step_fetcher(bus);
+            }
+
+            self.sprite_dots -= 1;
+            if self.sprite_dots == 0 {
+                self.merge_sprite(bus, sprite_index);
+                self.sprites_fetched[sprite_index] = true;
+                self.sprite_fetch = None;
+            }
+            return None;
+        }
+
+        self.step_fetcher(bus);
+
+        if self.bg_len == 0 {
+            return None;
+        }
+
+        let bg = self.bg_fifo[self.bg_index];
+        self.bg_index += 1;
+        self.bg_len -= 1;
+
+        let obj = self.obj_fifo[0];
+        self.obj_fifo.copy_within(1.., 0);
+        self.obj_fifo[FIFO_SIZE - 1] = FifoPixel::new();
+
+        if self.discard > 0 {
+            self.discard -= 1;
+            return None;
+        }
+
+        self.lcd_x += 1;
+
+        if obj.sprite && bus.sprite_wins(obj, bg) {
+            Some(obj)
+        } else {
+            Some(bg)
+        }
+    }
+}
+
+//Private methods
+impl PixelFifo {
+    fn step_fetcher(&mut self, bus: &PpuBus) {
+        if self.fetcher_step == FetcherStep::Push {
+            if self.bg_len == 0 {
+                for (column, pixel) in self.bg_fifo.iter_mut().enumerate() {
+                    let column = column as u8;
+                    let column = if self.tile_attributes & ATTR_X_FLIP > 0 {
+                        7 - column
+                    } else {
+                        column
+                    };
+                    *pixel = FifoPixel::new();
+                    pixel.color = self.tile_pixels[column as usize];
+                    pixel.flags = self.tile_attributes;
+                }
+                self.bg_index = 0;
+                self.bg_len = FIFO_SIZE;
+                self.fetch_column = self.fetch_column.wrapping_add(1);
+                self.fetcher_step = FetcherStep::Tile;
+            }
+            return;
+        }
+
+        self.fetcher_dots += 1;
+        if self.fetcher_dots < DOTS_PER_FETCH_STEP {
+            return;
+        }
+        self.fetcher_dots = 0;
+
+        //Registers are read when the step completes so mid-line writes affect the next fetch
+        match self.fetcher_step {
+            FetcherStep::Tile => {
+                let (map_address, column, pixel_y) = if self.window_active {
+                    (bus.window_map(), self.fetch_column, bus.window_line)
+                } else {
+                    let scx = bus.register(LCD_SCX_REG);
+                    let scy = bus.register(LCD_SCY_REG);
+                    (
+                        bus.bg_map(),
+                        (scx / 8).wrapping_add(self.fetch_column),
+                        bus.line.wrapping_add(scy),
+                    )
+                };
+                self.tile = bus.map_tile(map_address, column, pixel_y / 8);
+                self.tile_attributes = bus.map_attributes(map_address, column, pixel_y / 8);
+                self.tile_line = pixel_y % 8;
+                self.fetcher_step = FetcherStep::DataLow;
+            }
+            //Each bitplane is read on its own step like the hardware, so a vram write between the two steps (only
+            //possible with access blocking off) mixes the old low plane with the new high plane
+            FetcherStep::DataLow => {
+                self.tile_low = bus.bg_tile_byte(self.tile, self.tile_line, self.tile_attributes, LOW_PLANE);
+                self.fetcher_step = FetcherStep::DataHigh;
+            }
+            FetcherStep::DataHigh => {
+                let tile_high = bus.bg_tile_byte(self.tile, self.tile_line, self.tile_attributes, HIGH_PLANE);
+                self.tile_pixels = decode_tile_line(self.tile_low, tile_high);
+                self.fetcher_step = FetcherStep::Push;
+            }
+            FetcherStep::Push => {}
+        }
+    }
+
+    //Finds the first sprite of the line that starts at or before the current pixel and has not been fetched
+    fn next_sprite(&self, bus: &PpuBus) -> Option<usize> {
+        for (index, sprite) in bus.line_sprites.iter().enumerate() {
+            if !self.sprites_fetched[index] && bus.sprite_x(*sprite as usize) as u16 <= self.lcd_x as u16 + 8 {
+                return Some(index);
+            }
+        }
+
+        None
+    }
+
+    //On the DMG sprite pixels only replace transparent pixels of the object fifo, so earlier sprites keep priority.
+    //On the CGB a sprite with a lower OAM index also replaces pixels of sprites fetched before it.
+    fn merge_sprite(&mut self, bus: &PpuBus, sprite_index: usize) {
+        let sprite = bus.line_sprites[sprite_index] as usize;
+        let tile_pixels = bus.sprite_tile_line(sprite);
+        let flags = bus.sprite_flags(sprite);
+        let sprite_x = bus.sprite_x(sprite) as i16 - 8;
+
+        for column in 0..8 {
+            let x = sprite_x + column;
+            if x < self.lcd_x as i16 {
+                continue;
+            }
+
+            let slot = (x - self.lcd_x as i16) as usize;
+            if slot >= FIFO_SIZE {
+                continue;
+            }
+
+            let tile_column = if flags & SPRITE_X_FLIP > 0 { 7 - column } else { column };
+            let color = tile_pixels[tile_column as usize];
+            let current = self.obj_fifo[slot];
+            let replace = current.color == 0 || (bus.cgb_mode && color != 0 && (sprite as u8) < current.oam_index);
+            if replace {
+                self.obj_fifo[slot] = FifoPixel::sprite(color, flags, sprite as u8);
+            }
+        }
+    }
+}
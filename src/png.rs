@@ -0,0 +1,115 @@
+use std::vec::Vec;
+
+//Minimal PNG encoder for the debug images: https://www.w3.org/TR/png/
+//Pixels are written as 8-bit RGB.  The image data is stored in uncompressed deflate blocks so no compression
+//library is needed.  The files are larger than they could be but any viewer can open them.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGB: u8 = 2;
+const FILTER_NONE: u8 = 0;
+
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x01]; //Deflate with a 32K window, no dictionary
+const MAX_STORED_BLOCK: usize = 0xffff;
+const ADLER_MODULO: u32 = 65521;
+
+const CRC_TABLE: [u32; 256] = build_crc_table();
+
+//Encodes RGB888 pixels (0x00RRGGBB), left to right and top to bottom, as a PNG file
+pub fn encode_rgb(width: usize, height: usize, pixels: &[u32]) -> Vec<u8> {
+    //Every row starts with the filter type used for it
+    let mut raw = Vec::with_capacity(height * (width * 3 + 1));
+    for row in pixels.chunks(width).take(height) {
+        raw.push(FILTER_NONE);
+        for pixel in row {
+            raw.extend_from_slice(&pixel.to_be_bytes()[1..]);
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGB, 0, 0, 0]);
+
+    let mut png = Vec::new();
+    png.extend_from_slice(&SIGNATURE);
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+
+    //return
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+//Wraps the data in a zlib stream made of stored (uncompressed) deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(data.len() + data.len() / MAX_STORED_BLOCK * 5 + 11);
+    stream.extend_from_slice(&ZLIB_HEADER);
+
+    let mut blocks = data.chunks(MAX_STORED_BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+    }
+
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none();
+        let length = block.len() as u16;
+        stream.push(last as u8);
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    stream.extend_from_slice(&adler32(data).to_be_bytes());
+
+    //return
+    stream
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for byte in data {
+        a = (a + *byte as u32) % ADLER_MODULO;
+        b = (b + a) % ADLER_MODULO;
+    }
+
+    (b << 16) | a
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc = CRC_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+const fn build_crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 > 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+
+    table
+}
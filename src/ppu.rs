@@ -1,3 +1,9 @@
+use crate::color_profile::*;
+use crate::compat_palettes::*;
+use crate::debug_view::{self, SpriteInfo};
+use crate::frame::*;
+use crate::image::Image;
+use crate::pixel_fifo::*;
 use crate::vram::*;
 
 pub const LCD_ADDR_START: usize = 0xff40;
@@ -9,12 +15,110 @@ pub const SCREEN_RESOLUTION: usize = LCD_WIDTH * LCD_HEIGHT;
 const MEM_SIZE: usize = LCD_ADDR_END - LCD_ADDR_START + 1;
 
 pub const LCD_CRTL_REG: usize = 0xff40; //LCD Control Register
-
+pub const LCD_STAT_REG: usize = 0xff41; //LCD Status Register
+pub const LCD_SCY_REG: usize = 0xff42; //Scroll Y
+pub const LCD_SCX_REG: usize = 0xff43; //Scroll X
 pub const LCD_Y_REG: usize = 0xff44; //LCD Y-Coordinate (R)
+pub const LCD_LYC_REG: usize = 0xff45; //LY Compare
+pub const LCD_DMA_REG: usize = 0xff46; //OAM DMA source address
+pub const LCD_BGP_REG: usize = 0xff47; //Background Palette
+pub const LCD_OBP0_REG: usize = 0xff48; //Object Palette 0
+pub const LCD_OBP1_REG: usize = 0xff49; //Object Palette 1
+pub const LCD_WY_REG: usize = 0xff4a; //Window Y
+pub const LCD_WX_REG: usize = 0xff4b; //Window X + 7
+
+//CGB only registers
+pub const VRAM_BANK_REG: usize = 0xff4f; //VBK - Vram bank select
+pub const BCPS_REG: usize = 0xff68; //Background palette index
+pub const BCPD_REG: usize = 0xff69; //Background palette data
+pub const OCPS_REG: usize = 0xff6a; //Object palette index
+pub const OCPD_REG: usize = 0xff6b; //Object palette data
+
+//Each of the 8 palettes holds 4 RGB555 colors
+pub const CGB_PALETTE_RAM_SIZE: usize = 64;
+const PALETTE_AUTO_INCREMENT: u8 = 0x80;
+const PALETTE_INDEX_MASK: u8 = 0x3f;
+
+//Object attribute memory (sprite info)
+pub const OAM_START: usize = 0xfe00;
+pub const OAM_END: usize = 0xfe9f;
+pub const OAM_SIZE: usize = OAM_END - OAM_START + 1;
+pub const BYTES_PER_SPRITE: usize = 4;
+pub const MAX_SPRITES_PER_LINE: usize = 10;
+
+//LCDC bits
+pub const LCDC_BG_ENABLE: u8 = 0x01;
+pub const LCDC_OBJ_ENABLE: u8 = 0x02;
+pub const LCDC_OBJ_SIZE: u8 = 0x04;
+pub const LCDC_BG_MAP: u8 = 0x08;
+pub const LCDC_TILE_DATA: u8 = 0x10;
+pub const LCDC_WINDOW_ENABLE: u8 = 0x20;
+pub const LCDC_WINDOW_MAP: u8 = 0x40;
+pub const LCDC_LCD_ENABLE: u8 = 0x80;
+
+//STAT bits
+const STAT_MODE_MASK: u8 = 0x03;
+const STAT_LYC_EQUAL: u8 = 0x04;
+const STAT_HBLANK_INT: u8 = 0x08;
+const STAT_VBLANK_INT: u8 = 0x10;
+const STAT_OAM_INT: u8 = 0x20;
+const STAT_LYC_INT: u8 = 0x40;
+const STAT_WRITABLE_MASK: u8 = 0x78;
+
+//Sprite attribute flags
+pub const SPRITE_BG_PRIORITY: u8 = 0x80;
+pub const SPRITE_Y_FLIP: u8 = 0x40;
+pub const SPRITE_X_FLIP: u8 = 0x20;
+pub const SPRITE_DMG_PALETTE: u8 = 0x10;
+
+//CGB background map attributes (vram bank 1).  Palette and priority share their bit positions with the sprite flags.
+pub const ATTR_CGB_PALETTE: u8 = 0x07;
+pub const ATTR_VRAM_BANK: u8 = 0x08;
+pub const ATTR_X_FLIP: u8 = 0x20;
+pub const ATTR_Y_FLIP: u8 = 0x40;
+pub const ATTR_BG_PRIORITY: u8 = 0x80;
+
+//RGB555 colors used for the DMG shades
+const DMG_SHADES_RGB555: [u16; 4] = [0x7fff, 0x56b5, 0x294a, 0x0000];
+
+//Interrupt requests returned by update_lcd.  These match the bit positions of the IF register.
+pub const V_BLANK_REQUEST: u8 = 0x01;
+pub const LCD_STAT_REQUEST: u8 = 0x02;
+
+//Timing, in dots (1 dot = 1 T-cycle)
+pub const DOTS_PER_LINE: u32 = 456;
+pub const OAM_SCAN_DOTS: u32 = 80;
+pub const MIN_DRAWING_DOTS: u32 = 172;
+pub const LINES_PER_FRAME: u8 = 154;
+pub const DOTS_PER_FRAME: u32 = DOTS_PER_LINE * LINES_PER_FRAME as u32;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum PpuMode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+//Selects how mode 3 is emulated.  The scanline renderer draws a whole line at once which is cheap, the pixel fifo
+//renderer emulates the fetchers dot by dot so mid-scanline register writes are visible.
+#[derive(Copy, Clone, PartialEq)]
+pub enum Renderer {
+    Scanline,
+    PixelFifo,
+}
 
 pub struct Lcd {
     memory_registers: [u8; MEM_SIZE],
-    vram: Vram,
+    vram: [Vram; 2], //Bank 1 is only used in CGB mode.  It holds more tiles and the background map attributes.
+    vram_bank: usize,
+    cgb_mode: bool,
+    compat_mode: bool, //DMG game on CGB hardware.  The DMG shades are colored with CGB palettes.
+    bg_palettes: [u8; CGB_PALETTE_RAM_SIZE],
+    obj_palettes: [u8; CGB_PALETTE_RAM_SIZE],
+    bg_palette_index: u8,  //BCPS
+    obj_palette_index: u8, //OCPS
+    oam: [u8; OAM_SIZE],
     //0xff40: LCD Control Register
     //0xff41: LCD Status Register (R/W)
     //0xff42: SCY - Scroll Y (R/W)
@@ -27,35 +131,190 @@ pub struct Lcd {
     //0xff49: OBP1 - Object Palette 1 Data (R/W) - Non CGB Mode Only
     //0xff4a: WY - Window Y Position  (R/W)
     //0xff4b: WX - Window X Position minus 7  (R/W)
-    dot_counter: u32, //Keeps track of the number of dot clock cyles. Max 70244
+    dot_counter: u32, //Keeps track of the number of dot clock cyles. Max 70224
+    line_dot: u32,    //Dot within the current line. Max 455
     current_row: u8,
     current_col: u8,
+    mode: PpuMode,
+    renderer: Renderer,
+    fifo: PixelFifo,
+    drawing_dots: u32,                        //Length of mode 3 for the scanline renderer
+    line_sprites: [u8; MAX_SPRITES_PER_LINE], //OAM indexes found during the OAM scan
+    line_sprite_count: usize,
+    window_line: u8,        //Internal line counter of the window
+    window_triggered: bool, //Set once WY == LY during the frame
+    window_drawn: bool,     //The window was drawn on the current line
+    stat_line: bool,        //Previous state of the STAT interrupt line, interrupts trigger on its rising edge
+    access_blocking: bool,  //When false the cpu can always access vram and oam.  Used for debugging.
+    blocked_writes: u32,    //Number of cpu writes dropped because the ppu was using vram or oam
     screen_buffer_a: [u8; SCREEN_RESOLUTION],
     screen_buffer_b: [u8; SCREEN_RESOLUTION],
+    color_buffer_a: [u16; SCREEN_RESOLUTION], //RGB555
+    color_buffer_b: [u16; SCREEN_RESOLUTION],
+    draw_to_a: bool,   //The buffer not being drawn to holds the last completed frame
+    frame_ready: bool, //Set at the start of vblank when the buffers are swapped
+    color_profile: ColorProfile,
 }
 
 impl Lcd {
     pub fn new() -> Self {
         Self {
             memory_registers: [0; MEM_SIZE],
-            vram: Vram::new(),
+            vram: [Vram::new(), Vram::new()],
+            vram_bank: 0,
+            cgb_mode: false,
+            compat_mode: false,
+            bg_palettes: [0xff; CGB_PALETTE_RAM_SIZE],
+            obj_palettes: [0; CGB_PALETTE_RAM_SIZE],
+            bg_palette_index: 0,
+            obj_palette_index: 0,
+            oam: [0; OAM_SIZE],
             dot_counter: 0,
+            line_dot: 0,
             current_row: 0,
             current_col: 0,
+            mode: PpuMode::OamScan,
+            renderer: Renderer::Scanline,
+            fifo: PixelFifo::new(),
+            drawing_dots: MIN_DRAWING_DOTS,
+            line_sprites: [0; MAX_SPRITES_PER_LINE],
+            line_sprite_count: 0,
+            window_line: 0,
+            window_triggered: false,
+            window_drawn: false,
+            stat_line: false,
+            access_blocking: true,
+            blocked_writes: 0,
             screen_buffer_a: [0; SCREEN_RESOLUTION],
             screen_buffer_b: [0; SCREEN_RESOLUTION],
+            color_buffer_a: [0; SCREEN_RESOLUTION],
+            color_buffer_b: [0; SCREEN_RESOLUTION],
+            draw_to_a: true,
+            frame_ready: false,
+            color_profile: ColorProfile::new(),
+        }
+    }
+
+    pub fn set_renderer(&mut self, renderer: Renderer) {
+        self.renderer = renderer;
+    }
+
+    //In CGB mode the ppu uses vram bank 1, the color palettes and the background attributes
+    pub fn set_cgb_mode(&mut self, enabled: bool) {
+        self.cgb_mode = enabled;
+        if enabled {
+            self.compat_mode = false;
+        } else {
+            self.vram_bank = 0;
+        }
+    }
+
+    //Colors a DMG game the way the CGB boot rom does.  BG palette 0 and OBJ palettes 0 and 1 hold the colors.
+    pub fn load_compat_palette(&mut self, palette: &CompatPalette) {
+        self.set_cgb_mode(false);
+        self.compat_mode = true;
+
+        let palettes = [(&palette.bg, 0), (&palette.obj0, 0), (&palette.obj1, 1)];
+        for (i, (colors, number)) in palettes.iter().enumerate() {
+            let ram = if i == 0 {
+                &mut self.bg_palettes
+            } else {
+                &mut self.obj_palettes
+            };
+            for (shade, color) in colors.iter().enumerate() {
+                let rgb555 = rgb888_to_rgb555(*color);
+                let index = number * 8 + shade * 2;
+                ram[index] = rgb555 as u8;
+                ram[index + 1] = (rgb555 >> 8) as u8;
+            }
+        }
+    }
+
+    //Advances the ppu by a number of dots.  Returns the interrupts requested (see V_BLANK_REQUEST and LCD_STAT_REQUEST)
+    pub fn update_lcd(&mut self, dots: u32) -> u8 {
+        let mut requests = 0;
+
+        if self.lcd_enabled() {
+            for _ in 0..dots {
+                requests |= self.step_dot();
+            }
+        }
+
+        //return
+        requests
+    }
+
+    //Returns true once for every frame completed since the last call
+    pub fn frame_ready(&mut self) -> bool {
+        let ready = self.frame_ready;
+        self.frame_ready = false;
+        ready
+    }
+
+    //Borrows the last completed frame
+    pub fn frame(&self) -> Frame<'_> {
+        let dmg = !self.cgb_mode && !self.compat_mode;
+        if self.draw_to_a {
+            Frame::new(&self.screen_buffer_b, &self.color_buffer_b, dmg, &self.color_profile)
+        } else {
+            Frame::new(&self.screen_buffer_a, &self.color_buffer_a, dmg, &self.color_profile)
         }
     }
 
-    //Updates the current image buffer
-    pub fn update_lcd(&mut self) {}
+    //Image of every tile in vram.  In CGB mode bank 1 is drawn to the right of bank 0.
+    pub fn tile_sheet(&self, colors: [u32; 4]) -> Image {
+        let banks = if self.cgb_mode { 2 } else { 1 };
+        debug_view::tile_sheet(&self.vram[..banks], colors)
+    }
+
+    //Image of a whole background map with the screen and window outlined.  None draws the map used by the background.
+    pub fn tile_map(&self, map_address: Option<usize>, colors: [u32; 4]) -> Image {
+        let bus = self.bus();
+        debug_view::tile_map(&bus, map_address.unwrap_or_else(|| bus.bg_map()), colors)
+    }
+
+    //Every OAM entry with the number of lines it is drawn on, and dropped from, this frame
+    pub fn oam_info(&self) -> Vec<SpriteInfo> {
+        debug_view::oam_info(&self.bus())
+    }
+
+    //Image of the 40 sprites drawn on their own
+    pub fn sprite_sheet(&self, colors: [u32; 4]) -> Image {
+        debug_view::sprite_sheet(&self.bus(), colors)
+    }
+
+    //Colors used for the DMG shades when a frame is converted to RGB
+    pub fn set_dmg_palette(&mut self, palette: DmgPalette) {
+        self.color_profile.set_dmg_palette(palette);
+    }
+
+    //Correction applied to CGB colors when a frame is converted to RGB
+    pub fn set_color_correction(&mut self, correction: ColorCorrection) {
+        self.color_profile.set_correction(correction);
+    }
+
+    //Vram and oam are locked while the ppu reads them.  Blocked reads return 0xff and blocked writes are dropped.
+    pub fn set_access_blocking(&mut self, enabled: bool) {
+        self.access_blocking = enabled;
+    }
+
+    //Returns the number of writes dropped since the last call
+    pub fn take_blocked_writes(&mut self) -> u32 {
+        let count = self.blocked_writes;
+        self.blocked_writes = 0;
+        count
+    }
 
     pub fn read_vram(&self, address: usize) -> u8 {
+        if self.vram_blocked() {
+            return 0xff;
+        }
+
         let data: u8;
         if address <= BLOCK_2_END {
-            data = self.vram.read_vram_tile(address);
+            data = self.vram[self.vram_bank].read_vram_tile(address);
         } else {
-            data = self.vram.read_vram_map(address);
+            data = self.vram[self.vram_bank].read_vram_map(address);
         }
 
         //return
@@ -63,18 +322,863 @@ impl Lcd {
     }
 
     pub fn write_vram(&mut self, address: usize, data: u8) {
+        if self.vram_blocked() {
+            self.blocked_writes += 1;
+            return;
+        }
+
         if address <= BLOCK_2_END {
-            self.vram.write_vram_tile(address, data);
+            self.vram[self.vram_bank].write_vram_tile(address, data);
         } else {
-            self.vram.write_vram_map(address, data);
+            self.vram[self.vram_bank].write_vram_map(address, data);
         }
     }
 
+    pub fn read_oam(&self, address: usize) -> u8 {
+        if self.oam_blocked() {
+            return 0xff;
+        }
+
+        self.oam[address - OAM_START]
+    }
+
+    pub fn write_oam(&mut self, address: usize, data: u8) {
+        if self.oam_blocked() {
+            self.blocked_writes += 1;
+            return;
+        }
+
+        self.oam[address - OAM_START] = data;
+    }
+
+    //OAM DMA has its own path to oam and is not blocked by the ppu
+    pub fn dma_write_oam(&mut self, address: usize, data: u8) {
+        self.oam[address - OAM_START] = data;
+    }
+
     pub fn write_register(&mut self, index: usize, data: u8) {
-        self.memory_registers[index - LCD_ADDR_START] = data;
+        match index {
+            LCD_CRTL_REG => {
+                let was_enabled = self.read_register(LCD_CRTL_REG) & LCDC_LCD_ENABLE > 0;
+                self.memory_registers[index - LCD_ADDR_START] = data;
+                if was_enabled && data & LCDC_LCD_ENABLE == 0 {
+                    self.turn_off();
+                } else if !was_enabled && data & LCDC_LCD_ENABLE > 0 {
+                    self.turn_on();
+                }
+            }
+            LCD_STAT_REG => {
+                let stat = self.memory_registers[index - LCD_ADDR_START] & !STAT_WRITABLE_MASK;
+                self.memory_registers[index - LCD_ADDR_START] = stat | (data & STAT_WRITABLE_MASK);
+            }
+            LCD_Y_REG => {} //read only
+            LCD_LYC_REG => {
+                self.memory_registers[index - LCD_ADDR_START] = data;
+                self.compare_lyc();
+            }
+            _ => self.memory_registers[index - LCD_ADDR_START] = data,
+        }
     }
 
     pub fn read_register(&self, index: usize) -> u8 {
-        self.memory_registers[index - LCD_ADDR_START]
+        match index {
+            LCD_STAT_REG => self.memory_registers[index - LCD_ADDR_START] | 0x80, //bit 7 always reads 1
+            _ => self.memory_registers[index - LCD_ADDR_START],
+        }
+    }
+
+    //Writes VBK and the palette registers.  These registers do not exist outside of CGB mode.
+    pub fn write_cgb_register(&mut self, index: usize, data: u8) {
+        if !self.cgb_mode {
+            return;
+        }
+
+        match index {
+            VRAM_BANK_REG => self.vram_bank = (data & 0x01) as usize,
+            BCPS_REG => self.bg_palette_index = data & (PALETTE_AUTO_INCREMENT | PALETTE_INDEX_MASK),
+            OCPS_REG => self.obj_palette_index = data & (PALETTE_AUTO_INCREMENT | PALETTE_INDEX_MASK),
+            BCPD_REG => {
+                //Palette ram can't be written while the ppu is drawing, but the index still increments
+                if !self.vram_blocked() {
+                    self.bg_palettes[(self.bg_palette_index & PALETTE_INDEX_MASK) as usize] = data;
+                } else {
+                    self.blocked_writes += 1;
+                }
+                self.bg_palette_index = increment_palette_index(self.bg_palette_index);
+            }
+            OCPD_REG => {
+                if !self.vram_blocked() {
+                    self.obj_palettes[(self.obj_palette_index & PALETTE_INDEX_MASK) as usize] = data;
+                } else {
+                    self.blocked_writes += 1;
+                }
+                self.obj_palette_index = increment_palette_index(self.obj_palette_index);
+            }
+            _ => {}
+        }
+    }
+
+    pub fn read_cgb_register(&self, index: usize) -> u8 {
+        if !self.cgb_mode {
+            return 0xff;
+        }
+
+        match index {
+            VRAM_BANK_REG => 0xfe | self.vram_bank as u8,
+            BCPS_REG => self.bg_palette_index | 0x40, //bit 6 is unused and reads 1
+            OCPS_REG => self.obj_palette_index | 0x40,
+            BCPD_REG if !self.vram_blocked() => {
+                self.bg_palettes[(self.bg_palette_index & PALETTE_INDEX_MASK) as usize]
+            }
+            OCPD_REG if !self.vram_blocked() => {
+                self.obj_palettes[(self.obj_palette_index & PALETTE_INDEX_MASK) as usize]
+            }
+            _ => 0xff,
+        }
+    }
+}
+
+//Private methods
+impl Lcd {
+    //The pixel fifo builds its bus inline so it can borrow the fifo mutably at the same time
+    fn bus(&self) -> PpuBus<'_> {
+        PpuBus {
+            registers: &self.memory_registers,
+            vram: &self.vram,
+            oam: &self.oam,
+            bg_palettes: &self.bg_palettes,
+            obj_palettes: &self.obj_palettes,
+            cgb_mode: self.cgb_mode,
+            compat_mode: self.compat_mode,
+            line_sprites: &self.line_sprites[..self.line_sprite_count],
+            line: self.current_row,
+            window_line: self.window_line,
+            window_triggered: self.window_triggered,
+        }
+    }
+
+    fn step_dot(&mut self) -> u8 {
+        let mut requests = 0;
+
+        match self.mode {
+            PpuMode::OamScan => {
+                if self.line_dot == OAM_SCAN_DOTS - 1 {
+                    self.start_drawing();
+                }
+            }
+            PpuMode::Drawing => {
+                let finished = match self.renderer {
+                    Renderer::Scanline => self.line_dot + 1 >= OAM_SCAN_DOTS + self.drawing_dots,
+                    Renderer::PixelFifo => self.step_fifo(),
+                };
+
+                if finished {
+                    if self.renderer == Renderer::Scanline {
+                        self.render_scanline();
+                    }
+                    if self.window_drawn {
+                        self.window_line = self.window_line.wrapping_add(1);
+                    }
+                    self.set_mode(PpuMode::HBlank);
+                }
+            }
+            PpuMode::HBlank | PpuMode::VBlank => {}
+        }
+
+        self.dot_counter += 1;
+        self.line_dot += 1;
+        if self.line_dot == DOTS_PER_LINE {
+            self.line_dot = 0;
+            requests |= self.next_line();
+        }
+
+        requests | self.update_stat_line()
+    }
+
+    //Moves LY to the next line and handles the transitions into and out of vblank
+    fn next_line(&mut self) -> u8 {
+        let mut requests = 0;
+
+        self.current_row += 1;
+        if self.current_row == LINES_PER_FRAME {
+            self.current_row = 0;
+            self.dot_counter = 0;
+            self.window_line = 0;
+            self.window_triggered = false;
+        }
+        self.memory_registers[LCD_Y_REG - LCD_ADDR_START] = self.current_row;
+        self.compare_lyc();
+
+        if self.current_row as usize == LCD_HEIGHT {
+            self.set_mode(PpuMode::VBlank);
+            self.draw_to_a = !self.draw_to_a;
+            self.frame_ready = true;
+            requests |= V_BLANK_REQUEST;
+        } else if (self.current_row as usize) < LCD_HEIGHT {
+            self.start_oam_scan();
+        }
+
+        //return
+        requests
+    }
+
+    fn turn_off(&mut self) {
+        self.dot_counter = 0;
+        self.line_dot = 0;
+        self.current_row = 0;
+        self.current_col = 0;
+        self.window_line = 0;
+        self.window_triggered = false;
+        self.memory_registers[LCD_Y_REG - LCD_ADDR_START] = 0;
+        self.set_mode(PpuMode::HBlank);
+        self.stat_line = false;
+    }
+
+    fn turn_on(&mut self) {
+        self.compare_lyc();
+        self.start_oam_scan();
+    }
+
+    fn set_mode(&mut self, mode: PpuMode) {
+        self.mode = mode;
+        let stat = self.memory_registers[LCD_STAT_REG - LCD_ADDR_START] & !STAT_MODE_MASK;
+        self.memory_registers[LCD_STAT_REG - LCD_ADDR_START] = stat | mode as u8;
+    }
+
+    fn compare_lyc(&mut self) {
+        let stat = self.memory_registers[LCD_STAT_REG - LCD_ADDR_START] & !STAT_LYC_EQUAL;
+        if self.read_register(LCD_LYC_REG) == self.current_row {
+            self.memory_registers[LCD_STAT_REG - LCD_ADDR_START] = stat | STAT_LYC_EQUAL;
+        } else {
+            self.memory_registers[LCD_STAT_REG - LCD_ADDR_START] = stat;
+        }
+    }
+
+    //The STAT interrupt is requested on the rising edge of the OR of all enabled STAT sources
+    fn update_stat_line(&mut self) -> u8 {
+        let stat = self.memory_registers[LCD_STAT_REG - LCD_ADDR_START];
+        let line = (stat & STAT_LYC_INT > 0 && stat & STAT_LYC_EQUAL > 0)
+            || (stat & STAT_HBLANK_INT > 0 && self.mode == PpuMode::HBlank)
+            || (stat & STAT_VBLANK_INT > 0 && self.mode == PpuMode::VBlank)
+            || (stat & STAT_OAM_INT > 0 && self.mode == PpuMode::OamScan);
+
+        let rising_edge = line && !self.stat_line;
+        self.stat_line = line;
+
+        if rising_edge {
+            LCD_STAT_REQUEST
+        } else {
+            0
+        }
+    }
+
+    //Finds up to 10 sprites that are on the current line
+    fn start_oam_scan(&mut self) {
+        self.set_mode(PpuMode::OamScan);
+
+        if self.read_register(LCD_WY_REG) == self.current_row {
+            self.window_triggered = true;
+        }
+
+        let height = self.sprite_height() as i16;
+        let line = self.current_row as i16;
+        self.line_sprite_count = 0;
+
+        for sprite in 0..(OAM_SIZE / BYTES_PER_SPRITE) {
+            let y = self.oam[sprite * BYTES_PER_SPRITE] as i16 - 16;
+            if line >= y && line < y + height {
+                self.line_sprites[self.line_sprite_count] = sprite as u8;
+                self.line_sprite_count += 1;
+                if self.line_sprite_count == MAX_SPRITES_PER_LINE {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn start_drawing(&mut self) {
+        self.set_mode(PpuMode::Drawing);
+        self.current_col = 0;
+        self.window_drawn = false;
+
+        match self.renderer {
+            Renderer::Scanline => self.drawing_dots = self.estimate_drawing_dots(),
+            Renderer::PixelFifo => {
+                let scx = self.read_register(LCD_SCX_REG);
+                self.fifo.start_line(scx);
+            }
+        }
+    }
+
+    //Mode 3 length for the scanline renderer.  Approximates the penalties the fifo renderer emulates.
+    fn estimate_drawing_dots(&self) -> u32 {
+        let lcdc = self.read_register(LCD_CRTL_REG);
+        let scx = self.read_register(LCD_SCX_REG) as u32;
+        let mut dots = MIN_DRAWING_DOTS + (scx % 8);
+
+        if lcdc & LCDC_WINDOW_ENABLE > 0 && self.window_triggered && self.read_register(LCD_WX_REG) < 167 {
+            dots += 6;
+        }
+
+        if lcdc & LCDC_OBJ_ENABLE > 0 {
+            for i in 0..self.line_sprite_count {
+                let x = self.oam[self.line_sprites[i] as usize * BYTES_PER_SPRITE + 1];
+                if (x as usize) < LCD_WIDTH + 8 {
+                    dots += sprite_penalty(x, scx as u8);
+                }
+            }
+        }
+
+        //return
+        dots
+    }
+
+    //Runs the pixel fifo for one dot.  Returns true when the line is complete.
+    fn step_fifo(&mut self) -> bool {
+        let bus = PpuBus {
+            registers: &self.memory_registers,
+            vram: &self.vram,
+            oam: &self.oam,
+            bg_palettes: &self.bg_palettes,
+            obj_palettes: &self.obj_palettes,
+            cgb_mode: self.cgb_mode,
+            compat_mode: self.compat_mode,
+            line_sprites: &self.line_sprites[..self.line_sprite_count],
+            line: self.current_row,
+            window_line: self.window_line,
+            window_triggered: self.window_triggered,
+        };
+
+        if let Some(pixel) = self.fifo.step(&bus) {
+            let shade = bus.pixel_shade(pixel);
+            let color = bus.pixel_rgb555(pixel, shade);
+            self.put_pixel(self.current_col as usize, shade, color);
+            self.current_col += 1;
+        }
+        self.window_drawn = self.fifo.window_active();
+
+        //return
+        self.current_col as usize == LCD_WIDTH
+    }
+
+    //Draws the whole line at once
+    fn render_scanline(&mut self) {
+        let bus = self.bus();
+        let lcdc = bus.register(LCD_CRTL_REG);
+        let scx = bus.register(LCD_SCX_REG);
+        let scy = bus.register(LCD_SCY_REG);
+        let wx = bus.register(LCD_WX_REG);
+        let mut line_pixels = [FifoPixel::new(); LCD_WIDTH];
+        let mut window_drawn = false;
+
+        //Background and window
+        for (x, pixel) in line_pixels.iter_mut().enumerate() {
+            let in_window = lcdc & LCDC_WINDOW_ENABLE > 0 && bus.window_triggered && x + 7 >= wx as usize;
+            let (map_address, pixel_x, pixel_y) = if in_window {
+                window_drawn = true;
+                (bus.window_map(), (x + 7 - wx as usize) as u8, bus.window_line)
+            } else {
+                (bus.bg_map(), (x as u8).wrapping_add(scx), bus.line.wrapping_add(scy))
+            };
+
+            let tile = bus.map_tile(map_address, pixel_x / 8, pixel_y / 8);
+            let attributes = bus.map_attributes(map_address, pixel_x / 8, pixel_y / 8);
+            let tile_pixels = bus.bg_tile_line(tile, pixel_y % 8, attributes);
+            let column = if attributes & ATTR_X_FLIP > 0 {
+                7 - pixel_x % 8
+            } else {
+                pixel_x % 8
+            };
+            pixel.color = tile_pixels[column as usize];
+            pixel.flags = attributes;
+        }
+
+        //Sprites.  On the DMG the sprite with the smaller X wins, ties are won by the lower OAM index.
+        //On the CGB only the OAM index matters.
+        if lcdc & LCDC_OBJ_ENABLE > 0 {
+            let mut drawn = [false; LCD_WIDTH];
+            let mut order = [0u8; MAX_SPRITES_PER_LINE];
+            order[..bus.line_sprites.len()].copy_from_slice(bus.line_sprites);
+            let order = &mut order[..bus.line_sprites.len()];
+            if !bus.cgb_mode {
+                order.sort_by_key(|sprite| bus.sprite_x(*sprite as usize));
+            }
+
+            for sprite in order.iter() {
+                let sprite = *sprite as usize;
+                let tile_pixels = bus.sprite_tile_line(sprite);
+                let flags = bus.sprite_flags(sprite);
+                let sprite_x = bus.sprite_x(sprite) as i16 - 8;
+
+                for column in 0..8 {
+                    let x = sprite_x + column;
+                    if !(0..LCD_WIDTH as i16).contains(&x) || drawn[x as usize] {
+                        continue;
+                    }
+                    let column = if flags & SPRITE_X_FLIP > 0 { 7 - column } else { column };
+                    let color = tile_pixels[column as usize];
+                    if color == 0 {
+                        continue;
+                    }
+
+                    drawn[x as usize] = true;
+                    let bg = &mut line_pixels[x as usize];
+                    let obj = FifoPixel::sprite(color, flags, sprite as u8);
+                    if bus.sprite_wins(obj, *bg) {
+                        *bg = obj;
+                    }
+                }
+            }
+        }
+
+        let mut shades = [0u8; LCD_WIDTH];
+        let mut colors = [0u16; LCD_WIDTH];
+        for (x, pixel) in line_pixels.iter().enumerate() {
+            shades[x] = bus.pixel_shade(*pixel);
+            colors[x] = bus.pixel_rgb555(*pixel, shades[x]);
+        }
+
+        for x in 0..LCD_WIDTH {
+            self.put_pixel(x, shades[x], colors[x]);
+        }
+        self.current_col = LCD_WIDTH as u8;
+        self.window_drawn = window_drawn;
+    }
+
+    //Vram is in use by the ppu during mode 3
+    fn vram_blocked(&self) -> bool {
+        self.access_blocking && self.lcd_enabled() && self.mode == PpuMode::Drawing
+    }
+
+    //Oam is in use by the ppu during modes 2 and 3
+    fn oam_blocked(&self) -> bool {
+        self.access_blocking
+            && self.lcd_enabled()
+            && (self.mode == PpuMode::OamScan || self.mode == PpuMode::Drawing)
+    }
+
+    fn lcd_enabled(&self) -> bool {
+        self.read_register(LCD_CRTL_REG) & LCDC_LCD_ENABLE > 0
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.read_register(LCD_CRTL_REG) & LCDC_OBJ_SIZE > 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    //Writes a pixel of the current line to the buffers being drawn
+    fn put_pixel(&mut self, x: usize, shade: u8, color: u16) {
+        let index = self.current_row as usize * LCD_WIDTH + x;
+        if self.draw_to_a {
+            self.screen_buffer_a[index] = shade;
+            self.color_buffer_a[index] = color;
+        } else {
+            self.screen_buffer_b[index] = shade;
+            self.color_buffer_b[index] = color;
+        }
+    }
+}
+
+//Read only view of the ppu's memory used by the renderers while drawing a line
+pub struct PpuBus<'a> {
+    pub registers: &'a [u8; MEM_SIZE],
+    pub vram: &'a [Vram; 2],
+    pub oam: &'a [u8; OAM_SIZE],
+    pub bg_palettes: &'a [u8; CGB_PALETTE_RAM_SIZE],
+    pub obj_palettes: &'a [u8; CGB_PALETTE_RAM_SIZE],
+    pub cgb_mode: bool,
+    pub compat_mode: bool,
+    pub line_sprites: &'a [u8],
+    pub line: u8,
+    pub window_line: u8,
+    pub window_triggered: bool,
+}
+
+impl<'a> PpuBus<'a> {
+    #[inline]
+    pub fn register(&self, index: usize) -> u8 {
+        self.registers[index - LCD_ADDR_START]
+    }
+
+    pub fn bg_map(&self) -> usize {
+        if self.register(LCD_CRTL_REG) & LCDC_BG_MAP > 0 {
+            TILE_MAP_2_START
+        } else {
+            TILE_MAP_1_START
+        }
+    }
+
+    pub fn window_map(&self) -> usize {
+        if self.register(LCD_CRTL_REG) & LCDC_WINDOW_MAP > 0 {
+            TILE_MAP_2_START
+        } else {
+            TILE_MAP_1_START
+        }
+    }
+
+    //Reads the tile index at a tile column/row of a 32x32 tile map
+    pub fn map_tile(&self, map_address: usize, column: u8, row: u8) -> u8 {
+        self.vram[0].read_vram_map(map_address + (row as usize % 32) * 32 + (column as usize % 32))
+    }
+
+    //Reads the CGB attributes of a map entry.  These are stored at the same address in vram bank 1.
+    pub fn map_attributes(&self, map_address: usize, column: u8, row: u8) -> u8 {
+        if !self.cgb_mode {
+            return 0;
+        }
+
+        self.vram[1].read_vram_map(map_address + (row as usize % 32) * 32 + (column as usize % 32))
+    }
+
+    //Returns the decoded pixels of a background/window tile line.  Y flip and the tile's bank come from the
+    //map attributes.
+    pub fn bg_tile_line(&self, tile: u8, line: u8, attributes: u8) -> TilePixelLine {
+        let (address, line, bank) = self.bg_tile_location(tile, line, attributes);
+        self.tile_line(address, line, bank)
+    }
+
+    //Reads one bitplane byte of a background/window tile line, 0 for the low plane and 1 for the high plane.
+    //The pixel fifo fetches the two planes on separate steps.
+    pub fn bg_tile_byte(&self, tile: u8, line: u8, attributes: u8, plane: usize) -> u8 {
+        let (address, line, bank) = self.bg_tile_location(tile, line, attributes);
+        self.vram[bank].read_vram_tile(address + line as usize * BYTES_PER_TILE_LINE + plane)
+    }
+
+    //Returns the decoded pixels of the line of a sprite that is on the current LY
+    pub fn sprite_tile_line(&self, sprite: usize) -> TilePixelLine {
+        let entry = sprite * BYTES_PER_SPRITE;
+        let flags = self.oam[entry + 3];
+        let tall = self.register(LCD_CRTL_REG) & LCDC_OBJ_SIZE > 0;
+        let height: u8 = if tall { 16 } else { 8 };
+
+        let mut line = self.line.wrapping_add(16).wrapping_sub(self.oam[entry]) % height;
+        if flags & SPRITE_Y_FLIP > 0 {
+            line = height - 1 - line;
+        }
+
+        let mut tile = self.oam[entry + 2];
+        if tall {
+            tile &= 0xfe;
+        }
+
+        let bank = (self.cgb_mode && flags & ATTR_VRAM_BANK > 0) as usize;
+        let address = VRAM_START + tile as usize * BYTES_PER_TILE + (line as usize / 8) * BYTES_PER_TILE;
+        self.tile_line(address, line % 8, bank)
+    }
+
+    #[inline]
+    pub fn sprite_x(&self, sprite: usize) -> u8 {
+        self.oam[sprite * BYTES_PER_SPRITE + 1]
+    }
+
+    #[inline]
+    pub fn sprite_flags(&self, sprite: usize) -> u8 {
+        self.oam[sprite * BYTES_PER_SPRITE + 3]
+    }
+
+    //Decides if a sprite pixel is drawn over the background pixel below it
+    pub fn sprite_wins(&self, obj: FifoPixel, bg: FifoPixel) -> bool {
+        let lcdc = self.register(LCD_CRTL_REG);
+        if obj.color == 0 || lcdc & LCDC_OBJ_ENABLE == 0 {
+            return false;
+        }
+
+        if self.cgb_mode {
+            //In CGB mode LCDC bit 0 removes the priority of the background instead of blanking it
+            lcdc & LCDC_BG_ENABLE == 0
+                || bg.color == 0
+                || (obj.flags & SPRITE_BG_PRIORITY == 0 && bg.flags & ATTR_BG_PRIORITY == 0)
+        } else {
+            obj.flags & SPRITE_BG_PRIORITY == 0 || bg.color == 0 || lcdc & LCDC_BG_ENABLE == 0
+        }
+    }
+
+    //Maps a pixel to its RGB555 color.  In DMG mode this is the grey of the shade.
+    pub fn pixel_rgb555(&self, pixel: FifoPixel, shade: u8) -> u16 {
+        let palettes = if pixel.sprite {
+            self.obj_palettes
+        } else {
+            self.bg_palettes
+        };
+        let index = if self.cgb_mode {
+            (pixel.flags & ATTR_CGB_PALETTE) as usize * 8 + pixel.color as usize * 2
+        } else if self.compat_mode {
+            //The shade picked by BGP/OBP0/OBP1 indexes the colors of the boot rom's palettes
+            let number = (pixel.sprite && pixel.flags & SPRITE_DMG_PALETTE > 0) as usize;
+            number * 8 + shade as usize * 2
+        } else {
+            return DMG_SHADES_RGB555[shade as usize];
+        };
+
+        (palettes[index] as u16 | (palettes[index + 1] as u16) << 8) & 0x7fff
+    }
+
+    //Maps a pixel through the DMG palettes.  In CGB mode the shade is the pixel's color index.
+    pub fn pixel_shade(&self, pixel: FifoPixel) -> u8 {
+        if self.cgb_mode {
+            return pixel.color;
+        }
+
+        let lcdc = self.register(LCD_CRTL_REG);
+        let (palette, color) = if pixel.sprite {
+            let palette = if pixel.flags & SPRITE_DMG_PALETTE > 0 {
+                self.register(LCD_OBP1_REG)
+            } else {
+                self.register(LCD_OBP0_REG)
+            };
+            (palette, pixel.color)
+        } else if lcdc & LCDC_BG_ENABLE > 0 {
+            (self.register(LCD_BGP_REG), pixel.color)
+        } else {
+            //On the DMG clearing LCDC bit 0 blanks the background and window
+            (self.register(LCD_BGP_REG), 0)
+        };
+
+        (palette >> (color * 2)) & 0x3
+    }
+
+    #[inline]
+    fn tile_line(&self, tile_address: usize, line: u8, bank: usize) -> TilePixelLine {
+        self.vram[bank].tile_line_at(tile_address, line)
+    }
+
+    //Returns the tile's address, the line after y flip and the vram bank of a background/window tile
+    fn bg_tile_location(&self, tile: u8, line: u8, attributes: u8) -> (usize, u8, usize) {
+        let address = if self.register(LCD_CRTL_REG) & LCDC_TILE_DATA > 0 {
+            VRAM_START + tile as usize * BYTES_PER_TILE
+        } else {
+            //8800 addressing uses 0x9000 as the base and a signed tile index
+            (BLOCK_2_START as isize + (tile as i8 as isize) * BYTES_PER_TILE as isize) as usize
+        };
+
+        let line = if attributes & ATTR_Y_FLIP > 0 { 7 - line } else { line };
+        (address, line, (attributes & ATTR_VRAM_BANK > 0) as usize)
+    }
+}
+
+//BCPS/OCPS increment after a data write when bit 7 is set
+fn increment_palette_index(index: u8) -> u8 {
+    if index & PALETTE_AUTO_INCREMENT > 0 {
+        PALETTE_AUTO_INCREMENT | (index.wrapping_add(1) & PALETTE_INDEX_MASK)
+    } else {
+        index
+    }
+}
+
+//Number of dots a sprite adds to mode 3.  The fetch takes 6 dots plus the time the background fetcher needs to
+//finish the tile under the sprite's left most pixel.  A sprite at X = 0 always costs 11 dots.
+pub fn sprite_penalty(sprite_x: u8, scx: u8) -> u32 {
+    if sprite_x == 0 {
+        return 11;
+    }
+
+    let offset = (sprite_x.wrapping_add(scx) % 8) as u32;
+    6 + 5 - offset.min(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Fills 4 tiles with different patterns, the background map with a checkerboard of them and the window map with
+    //tile 3.  The lcd is left off so the registers can be set before the first line.
+    fn test_lcd(renderer: Renderer) -> Lcd {
+        let mut lcd = Lcd::new();
+        lcd.set_renderer(renderer);
+
+        for tile in 0..4usize {
+            for byte in 0..BYTES_PER_TILE {
+                let pattern = match tile {
+                    0 => 0x00,
+                    1 => 0xf0,
+                    2 => (byte as u8).wrapping_mul(37),
+                    _ => 0xaa,
+                };
+                lcd.write_vram(VRAM_START + tile * BYTES_PER_TILE + byte, pattern);
+            }
+        }
+        for entry in 0..32 * 32 {
+            lcd.write_vram(TILE_MAP_1_START + entry, ((entry + entry / 32) % 3) as u8);
+            lcd.write_vram(TILE_MAP_2_START + entry, 3);
+        }
+
+        lcd.write_register(LCD_BGP_REG, 0xe4);
+        lcd.write_register(LCD_OBP0_REG, 0xd2);
+        lcd
+    }
+
+    //Uses 8000 addressing and the 9c00 window map, with the background, window and sprites enabled
+    fn turn_on(lcd: &mut Lcd) {
+        lcd.write_register(
+            LCD_CRTL_REG,
+            LCDC_LCD_ENABLE
+                | LCDC_WINDOW_MAP
+                | LCDC_WINDOW_ENABLE
+                | LCDC_TILE_DATA
+                | LCDC_OBJ_ENABLE
+                | LCDC_BG_ENABLE,
+        );
+    }
+
+    fn add_sprite(lcd: &mut Lcd, index: usize, x: u8, y: u8, tile: u8) {
+        let entry = OAM_START + index * BYTES_PER_SPRITE;
+        lcd.dma_write_oam(entry, y);
+        lcd.dma_write_oam(entry + 1, x);
+        lcd.dma_write_oam(entry + 2, tile);
+        lcd.dma_write_oam(entry + 3, 0);
+    }
+
+    //Runs the ppu to the start of a line of the current frame
+    fn run_to_line(lcd: &mut Lcd, line: u8) {
+        while lcd.current_row != line || lcd.line_dot != 0 {
+            lcd.update_lcd(1);
+        }
+    }
+
+    //Length of mode 3 on the next line drawn
+    fn drawing_dots(lcd: &mut Lcd) -> u32 {
+        while lcd.mode != PpuMode::Drawing {
+            lcd.update_lcd(1);
+        }
+        let mut dots = 0;
+        while lcd.mode == PpuMode::Drawing {
+            lcd.update_lcd(1);
+            dots += 1;
+        }
+        dots
+    }
+
+    #[test]
+    fn sprite_penalty_depends_on_the_position_in_the_background_tile() {
+        assert_eq!(sprite_penalty(0, 0), 11);
+        assert_eq!(sprite_penalty(8, 0), 11);
+        assert_eq!(sprite_penalty(9, 0), 10);
+        assert_eq!(sprite_penalty(13, 0), 6);
+        assert_eq!(sprite_penalty(15, 0), 6);
+        assert_eq!(sprite_penalty(8, 3), 8);
+    }
+
+    #[test]
+    fn fifo_mode_3_length_includes_scx_and_sprite_penalties() {
+        let mut lcd = test_lcd(Renderer::PixelFifo);
+        lcd.write_register(LCD_WX_REG, 200);
+        turn_on(&mut lcd);
+        assert_eq!(drawing_dots(&mut lcd), MIN_DRAWING_DOTS);
+
+        lcd.write_register(LCD_SCX_REG, 3);
+        run_to_line(&mut lcd, 1);
+        assert_eq!(drawing_dots(&mut lcd), MIN_DRAWING_DOTS + 3);
+
+        lcd.write_register(LCD_SCX_REG, 0);
+        add_sprite(&mut lcd, 0, 8, 18, 2);
+        add_sprite(&mut lcd, 1, 45, 18, 2);
+        run_to_line(&mut lcd, 2);
+        assert_eq!(
+            drawing_dots(&mut lcd),
+            MIN_DRAWING_DOTS + sprite_penalty(8, 0) + sprite_penalty(45, 0)
+        );
+    }
+
+    #[test]
+    fn fifo_renderer_draws_the_same_frame_as_the_scanline_renderer() {
+        let mut frames = Vec::new();
+        for renderer in [Renderer::Scanline, Renderer::PixelFifo] {
+            let mut lcd = test_lcd(renderer);
+            lcd.write_register(LCD_SCX_REG, 13);
+            lcd.write_register(LCD_SCY_REG, 5);
+            lcd.write_register(LCD_WY_REG, 90);
+            lcd.write_register(LCD_WX_REG, 87);
+            add_sprite(&mut lcd, 0, 20, 40, 2);
+            add_sprite(&mut lcd, 1, 24, 44, 1);
+            add_sprite(&mut lcd, 2, 100, 120, 2);
+            turn_on(&mut lcd);
+
+            lcd.update_lcd(DOTS_PER_FRAME);
+            assert!(lcd.frame_ready());
+            frames.push(lcd.frame().shades().to_vec());
+        }
+
+        assert!(frames[0] == frames[1]);
+    }
+
+    //Draws a frame with the scanline renderer, which uses the registers as they are at the start of each line
+    fn scanline_frame(bgp: u8) -> Vec<u8> {
+        let mut lcd = test_lcd(Renderer::Scanline);
+        lcd.write_register(LCD_BGP_REG, bgp);
+        lcd.write_register(LCD_WX_REG, 200);
+        turn_on(&mut lcd);
+        lcd.update_lcd(DOTS_PER_FRAME);
+        lcd.frame().shades().to_vec()
+    }
+
+    //Like mealybug-tearoom's m3_bgp_change: pixels already pushed keep the old palette and the rest of the line
+    //uses the new one
+    #[test]
+    fn fifo_renderer_applies_a_bgp_change_from_the_next_pixel_pushed() {
+        const LINE: usize = 50;
+        const CHANGE_DOT: usize = 80; //Into mode 3
+        let old = scanline_frame(0xe4);
+        let new = scanline_frame(0x1b); //Every shade is different
+
+        let mut lcd = test_lcd(Renderer::PixelFifo);
+        lcd.write_register(LCD_WX_REG, 200);
+        turn_on(&mut lcd);
+        run_to_line(&mut lcd, LINE as u8);
+        while lcd.mode != PpuMode::Drawing {
+            lcd.update_lcd(1);
+        }
+        lcd.update_lcd(CHANGE_DOT as u32);
+        lcd.write_register(LCD_BGP_REG, 0x1b);
+        run_to_line(&mut lcd, LINE as u8 + 1);
+        lcd.write_register(LCD_BGP_REG, 0xe4);
+        lcd.update_lcd(DOTS_PER_FRAME);
+
+        let shades = lcd.frame().shades();
+        let row = LINE * LCD_WIDTH..(LINE + 1) * LCD_WIDTH;
+        let switch = (0..LCD_WIDTH)
+            .position(|x| shades[row.start + x] != old[row.start + x])
+            .unwrap();
+        assert!(shades[row.start..row.start + switch] == old[row.start..row.start + switch]);
+        assert!(shades[row.start + switch..row.end] == new[row.start + switch..row.end]);
+        //Mode 3 spends the dots beyond the 160 pixels before the first pixel is pushed
+        assert_eq!(switch, CHANGE_DOT - (MIN_DRAWING_DOTS as usize - LCD_WIDTH));
+
+        //The lines around it are drawn with the palette they started with
+        assert!(shades[row.end..row.end + LCD_WIDTH] == old[row.end..row.end + LCD_WIDTH]);
+        assert!(shades[row.start - LCD_WIDTH..row.start] == old[row.start - LCD_WIDTH..row.start]);
+    }
+
+    #[test]
+    fn window_line_only_counts_lines_the_window_was_drawn_on() {
+        let mut lcd = test_lcd(Renderer::PixelFifo);
+        lcd.write_register(LCD_WY_REG, 10);
+        lcd.write_register(LCD_WX_REG, 7);
+        turn_on(&mut lcd);
+
+        run_to_line(&mut lcd, 20);
+        assert_eq!(lcd.window_line, 10);
+
+        //Disabling the window pauses the counter instead of letting it follow LY
+        let lcdc = lcd.read_register(LCD_CRTL_REG);
+        lcd.write_register(LCD_CRTL_REG, lcdc & !LCDC_WINDOW_ENABLE);
+        run_to_line(&mut lcd, 30);
+        assert_eq!(lcd.window_line, 10);
+
+        //So does moving it off screen
+        lcd.write_register(LCD_CRTL_REG, lcdc);
+        lcd.write_register(LCD_WX_REG, 200);
+        run_to_line(&mut lcd, 35);
+        assert_eq!(lcd.window_line, 10);
+
+        lcd.write_register(LCD_WX_REG, 7);
+        run_to_line(&mut lcd, 40);
+        assert_eq!(lcd.window_line, 15);
+
+        //The counter restarts with the frame
+        run_to_line(&mut lcd, 0);
+        assert_eq!(lcd.window_line, 0);
     }
 }
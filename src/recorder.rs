@@ -0,0 +1,248 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::vec::Vec;
+
+use crate::cpu::CPU_CLOCK_HZ;
+use crate::frame::{Frame, PixelFormat};
+use crate::gif::{self, GifFrame};
+use crate::ppu::*;
+use crate::wav::WavWriter;
+
+//Records the frames to a Y4M video and the sound to a WAV file for bug reports.  Both can be converted offline, e.g.
+//ffmpeg -i game.y4m -i game.wav game.mp4
+//The streams are kept in sync with the emulated cycle count: frames the lcd did not draw (lcd off) repeat the last
+//frame, and the audio is padded with silence to the length of the video when the recording finishes.
+
+const AUDIO_CHANNELS: u16 = 2;
+//4:4:4 so no color resolution is lost, full range since the colors come from a computer display.  Rounding in the
+//RGB to YUV conversion is the only loss.
+const Y4M_HEADER: &str = "YUV4MPEG2 W160 H144 F4194304:70224 Ip A1:1 C444 XCOLORRANGE=FULL\n";
+const Y4M_FRAME_HEADER: &[u8] = b"FRAME\n";
+
+pub struct Recorder {
+    video: BufWriter<File>,
+    audio: WavWriter,
+    sample_rate: u32,
+    frames_written: u64,
+    samples_written: u64, //Per channel
+    last_frame: Vec<u8>,  //Y, U and V planes of the last frame, repeated while the lcd is off
+}
+
+impl Recorder {
+    //Creates <path>.y4m and <path>.wav
+    pub fn start(path: &str, sample_rate: u32) -> io::Result<Self> {
+        let mut video = BufWriter::new(File::create(format!("{}.y4m", path))?);
+        video.write_all(Y4M_HEADER.as_bytes())?;
+
+        Ok(Self {
+            video,
+            audio: WavWriter::create(&format!("{}.wav", path), sample_rate, AUDIO_CHANNELS)?,
+            sample_rate,
+            frames_written: 0,
+            samples_written: 0,
+            last_frame: vec![0; SCREEN_RESOLUTION * 3],
+        })
+    }
+
+    //Adds a completed frame.  `cycle` is the cpu's cycle count when the frame finished.
+    pub fn push_frame(&mut self, frame: &Frame, cycle: u64) -> io::Result<()> {
+        let frame_index = cycle / DOTS_PER_FRAME as u64;
+
+        //The lcd was off, keep showing the last picture for the missing frames
+        while self.frames_written < frame_index {
+            self.write_last_frame()?;
+        }
+
+        //The lcd was turned back on part way through a frame, the video is already at this point in time
+        if self.frames_written > frame_index {
+            return Ok(());
+        }
+
+        let (y_plane, uv_planes) = self.last_frame.split_at_mut(SCREEN_RESOLUTION);
+        let (u_plane, v_plane) = uv_planes.split_at_mut(SCREEN_RESOLUTION);
+        for index in 0..SCREEN_RESOLUTION {
+            let (y, u, v) = rgb_to_yuv(frame.pixel_rgb888(index % LCD_WIDTH, index / LCD_WIDTH));
+            y_plane[index] = y;
+            u_plane[index] = u;
+            v_plane[index] = v;
+        }
+
+        self.write_last_frame()?;
+        self.audio.flush()
+    }
+
+    //Adds stereo samples (left, right, ...) at the recorder's sample rate
+    pub fn push_audio(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.samples_written += (samples.len() / AUDIO_CHANNELS as usize) as u64;
+        self.audio.write_samples(samples)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        //Pad the audio so both files have the same length
+        let video_cycles = self.frames_written * DOTS_PER_FRAME as u64;
+        let video_samples = video_cycles * self.sample_rate as u64 / CPU_CLOCK_HZ;
+        if self.samples_written < video_samples {
+            let silence = vec![0i16; (video_samples - self.samples_written) as usize * AUDIO_CHANNELS as usize];
+            self.push_audio(&silence)?;
+        }
+
+        self.video.flush()?;
+        self.audio.finish()
+    }
+}
+
+//Private methods
+impl Recorder {
+    fn write_last_frame(&mut self) -> io::Result<()> {
+        self.video.write_all(Y4M_FRAME_HEADER)?;
+        self.video.write_all(&self.last_frame)?;
+        self.frames_written += 1;
+
+        Ok(())
+    }
+}
+
+//Full range BT.601
+fn rgb_to_yuv(rgb: u32) -> (u8, u8, u8) {
+    let r = ((rgb >> 16) & 0xff) as f32;
+    let g = ((rgb >> 8) & 0xff) as f32;
+    let b = (rgb & 0xff) as f32;
+
+    let y = 0.299 * r + 0.587 * g + 0.114 * b;
+    let u = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+    let v = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+
+    let clamp = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    (clamp(y), clamp(u), clamp(v))
+}
+
+//Writes every frame back to back in one of the frame pixel formats, with no header.  Meant for piping into other
+//tools or testing a display driver, e.g. ffmpeg -f rawvideo -pix_fmt rgb565be -s 160x144 -i game.raw game.mp4
+pub struct RawVideo {
+    file: BufWriter<File>,
+    format: PixelFormat,
+    buffer: Vec<u8>,
+}
+
+impl RawVideo {
+    pub fn create(path: &str, format: PixelFormat) -> io::Result<Self> {
+        Ok(Self {
+            file: BufWriter::new(File::create(path)?),
+            format,
+            buffer: vec![0; format.frame_size()],
+        })
+    }
+
+    pub fn push_frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let size = frame.write_to(self.format, &mut self.buffer);
+        self.file.write_all(&self.buffer[..size])
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+//Keeps the last seconds of gameplay in memory so they can be saved as a GIF after a bug shows up.
+//Every other frame is kept, GIF players don't handle delays below 2/100 of a second.
+const GIF_FRAME_STEP: u32 = 2;
+const CENTISECONDS: u64 = 100;
+pub const GIF_SECONDS: u32 = 10; //About 28 MB of frames
+
+pub struct GifCapture {
+    frames: VecDeque<(Vec<u32>, u64)>, //RGB888 pixels and the cycle count of the frame
+    capacity: usize,
+    frame_counter: u32,
+}
+
+impl GifCapture {
+    pub fn new(seconds: u32) -> Self {
+        let frames_per_second = CPU_CLOCK_HZ / DOTS_PER_FRAME as u64 / GIF_FRAME_STEP as u64;
+        let capacity = (seconds as u64 * frames_per_second).max(1) as usize;
+
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity,
+            frame_counter: 0,
+        }
+    }
+
+    //Adds a completed frame.  The oldest frame is dropped once the buffer is full.
+    pub fn push_frame(&mut self, frame: &Frame, cycle: u64) {
+        self.frame_counter = (self.frame_counter + 1) % GIF_FRAME_STEP;
+        if self.frame_counter != 0 {
+            return;
+        }
+
+        //Reuse the oldest frame's buffer
+        let mut pixels = if self.frames.len() == self.capacity {
+            self.frames.pop_front().map(|(pixels, _)| pixels).unwrap_or_default()
+        } else {
+            Vec::with_capacity(SCREEN_RESOLUTION)
+        };
+        pixels.clear();
+        pixels
+            .extend((0..SCREEN_RESOLUTION).map(|index| frame.pixel_rgb888(index % LCD_WIDTH, index / LCD_WIDTH)));
+
+        self.frames.push_back((pixels, cycle));
+    }
+
+    pub fn to_gif(&self) -> Vec<u8> {
+        //Delays are rounded from the emulated time so the GIF plays at the real speed on average
+        let start = self.frames.front().map(|(_, cycle)| *cycle).unwrap_or(0);
+        let centiseconds = |cycle: u64| (cycle - start) * CENTISECONDS / CPU_CLOCK_HZ;
+
+        let frames: Vec<GifFrame> = self
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(index, (pixels, cycle))| {
+                let next_cycle = match self.frames.get(index + 1) {
+                    Some((_, next)) => *next,
+                    None => cycle + (DOTS_PER_FRAME * GIF_FRAME_STEP) as u64,
+                };
+                GifFrame {
+                    pixels,
+                    delay_centiseconds: (centiseconds(next_cycle) - centiseconds(*cycle)).min(u16::MAX as u64)
+                        as u16,
+                }
+            })
+            .collect();
+
+        gif::encode(LCD_WIDTH, LCD_HEIGHT, &frames)
+    }
+
+    pub fn save_gif(&self, path: &str) -> io::Result<()> {
+        fs::write(path, self.to_gif())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color_profile::ColorProfile;
+
+    #[test]
+    fn gif_capture_keeps_every_other_frame_of_the_last_seconds() {
+        let profile = ColorProfile::new();
+        let colors = [0u16; SCREEN_RESOLUTION];
+        let mut capture = GifCapture::new(1);
+        assert_eq!(capture.capacity, 29);
+
+        for index in 0..100u64 {
+            let shades = [(index % 4) as u8; SCREEN_RESOLUTION];
+            capture.push_frame(
+                &Frame::new(&shades, &colors, true, &profile),
+                index * DOTS_PER_FRAME as u64,
+            );
+        }
+
+        assert_eq!(capture.frames.len(), capture.capacity);
+        //The frames pushed at indexes 43, 45, ... 99 are kept
+        let (pixels, cycle) = capture.frames.back().unwrap();
+        assert_eq!(*cycle, 99 * DOTS_PER_FRAME as u64);
+        assert_eq!(pixels[0], profile.shade_to_rgb888(3));
+        assert_eq!(capture.frames.front().unwrap().1, 43 * DOTS_PER_FRAME as u64);
+    }
+}
@@ -0,0 +1,88 @@
+use std::f64::consts::PI;
+use std::vec::Vec;
+
+//Band limited resampler from the apu clock to the host's sample rate.
+//The apu output is a sum of steps.  Each change of level is added as a windowed sinc impulse at its exact position
+//between two output samples, and the output is the running sum of those impulses.  This removes the aliasing a
+//plain decimation would give to the square waves.
+
+const TAPS: usize = 16; //Length of the impulse in output samples
+const PHASES: usize = 64; //Sub sample positions the impulse is computed for
+const CUTOFF: f64 = 0.9; //Fraction of the output's Nyquist frequency kept
+
+pub struct Resampler {
+    samples_per_clock: f64,
+    buffer: Vec<f32>, //Impulses added so far.  The output is their running sum.
+    position: f64,    //Output position of the start of the current time frame
+    integrator: f32,
+    kernel: Vec<[f32; TAPS]>, //One impulse per phase, each summing to 1
+}
+
+impl Resampler {
+    pub fn new(clock_rate: u32, sample_rate: u32) -> Self {
+        let mut kernel = vec![[0.0; TAPS]; PHASES + 1];
+        for (phase, taps) in kernel.iter_mut().enumerate() {
+            let center = (TAPS / 2) as f64 + phase as f64 / PHASES as f64;
+            let mut sum = 0.0;
+            for (tap, value) in taps.iter_mut().enumerate() {
+                let x = tap as f64 - center;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (PI * CUTOFF * x).sin() / (PI * CUTOFF * x)
+                };
+                //Blackman window over the length of the impulse
+                let w = (x + (TAPS / 2) as f64) / TAPS as f64;
+                let window = 0.42 - 0.5 * (2.0 * PI * w).cos() + 0.08 * (4.0 * PI * w).cos();
+                let tap_value = if (0.0..=1.0).contains(&w) { sinc * window } else { 0.0 };
+                *value = tap_value as f32;
+                sum += tap_value;
+            }
+            for value in taps.iter_mut() {
+                *value /= sum as f32;
+            }
+        }
+
+        Self {
+            samples_per_clock: sample_rate as f64 / clock_rate as f64,
+            buffer: vec![0.0; TAPS],
+            position: 0.0,
+            integrator: 0.0,
+            kernel,
+        }
+    }
+
+    //Adds a change of level happening `clock` clocks after the start of the current time frame
+    pub fn add_delta(&mut self, clock: u32, delta: f32) {
+        let position = self.position + clock as f64 * self.samples_per_clock;
+        let index = position as usize;
+        let phase = ((position - index as f64) * PHASES as f64).round() as usize;
+
+        if self.buffer.len() < index + TAPS {
+            self.buffer.resize(index + TAPS, 0.0);
+        }
+        for (sample, tap) in self.buffer[index..index + TAPS]
+            .iter_mut()
+            .zip(self.kernel[phase].iter())
+        {
+            *sample += delta * tap;
+        }
+    }
+
+    //Ends the current time frame after a number of clocks and appends the completed output samples
+    pub fn end_frame(&mut self, clocks: u32, output: &mut Vec<f32>) {
+        self.position += clocks as f64 * self.samples_per_clock;
+
+        //Later impulses can't reach samples before the current position
+        let completed = self.position as usize;
+        if self.buffer.len() < completed + TAPS {
+            self.buffer.resize(completed + TAPS, 0.0);
+        }
+        for delta in self.buffer.drain(..completed) {
+            self.integrator += delta;
+            output.push(self.integrator);
+        }
+
+        self.position -= completed as f64;
+    }
+}
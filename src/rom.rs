@@ -3,6 +3,10 @@ pub const ROM_BANK_00_END: usize = 0x3fff;
 pub const ROM_BANK_01_START: usize = 0x4000;
 pub const ROM_BANK_01_END: usize = 0x7fff;
 
+//Cartridge header
+pub const CGB_FLAG_ADDR: usize = 0x0143; //0x80: CGB enhanced, 0xC0: CGB only
+pub const CGB_FLAG_MASK: u8 = 0x80;
+
 #[derive(Copy, Clone)]
 pub struct Rom {
     rom: [u8; ROM_BANK_00_END + 1],
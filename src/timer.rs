@@ -1,5 +1,8 @@
 pub const TIMER_ADDR_START: usize = 0xff04;
 pub const TIMER_ADDR_END: usize = 0xff07;
+pub const DIV_REG: usize = 0xff04;
+pub const TMA_REG: usize = 0xff06;
+pub const TAC_REG: usize = 0xff07;
 
 //These are the four register reprsented by the array
 const DIV: usize = 0;
@@ -7,74 +10,272 @@ const TIMA: usize = 1;
 const TMA: usize = 2;
 const TAC: usize = 3;
 
+//Both timers run off a 16-bit system counter incremented every T-cycle: https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html
+//DIV is the counter's upper byte.  TIMA counts the falling edges of the counter bit selected by TAC, AND'ed with the
+//enable bit, so resetting DIV or changing TAC can increment TIMA.
+const TAC_ENABLE: u8 = 0x04;
+const TAC_CLOCK_SELECT: u8 = 0x03;
+const TAC_UNUSED_BITS: u8 = 0xf8; //Read back as 1
+const TAC_COUNTER_BITS: [u16; 4] = [9, 3, 5, 7]; //4096 Hz, 262144 Hz, 65536 Hz, 16384 Hz
+const RELOAD_STEP_CYCLES: u8 = 4; //Each step of the reload lasts one M-cycle
+
+//After overflowing TIMA reads 0 for one M-cycle, then it's reloaded from TMA and the interrupt is requested.
+//Writing TIMA during the first M-cycle cancels the reload.  During the reload M-cycle TIMA writes are ignored and
+//TMA writes go through to TIMA as well.
+#[derive(Copy, Clone, PartialEq)]
+enum TimaReload {
+    Idle,
+    Overflowed,
+    Reloading,
+}
+
 pub struct Timer {
-    source_timer: u8,
-    div_prescaler: u8,
-    update_source: u8,
-    //0xff04 - 0xff07
+    system_counter: u16,
+    tima_reload: TimaReload,
+    reload_cycles: u8,       //T-cycles left in the current reload step
+    interrupt_request: bool, //TIMA was reloaded since the last update
+    //0xff04 - 0xff07.  DIV is not stored, it's read from the system counter.
     memory_registers: [u8; TIMER_ADDR_END - TIMER_ADDR_START + 1],
 }
 
 impl Timer {
     pub fn new() -> Self {
         Self {
-            source_timer: 0,
-            div_prescaler: 0,
-            update_source: 0,
+            system_counter: 0,
+            tima_reload: TimaReload::Idle,
+            reload_cycles: 0,
+            interrupt_request: false,
             memory_registers: [0, 0, 0, 0],
         }
     }
 
     //Reads the timer regsiters.
     pub fn read_memory(&self, index: usize) -> u8 {
-        self.memory_registers[index - TIMER_ADDR_START]
+        match index - TIMER_ADDR_START {
+            DIV => (self.system_counter >> 8) as u8,
+            TAC => self.memory_registers[TAC] | TAC_UNUSED_BITS,
+            register => self.memory_registers[register],
+        }
     }
 
-    //Write to the timer registers.
+    //Write to the timer registers.  Any write to DIV resets the whole system counter.
     pub fn write_memory(&mut self, index: usize, value: u8) {
-        self.memory_registers[index - TIMER_ADDR_START] = value;
-    }
+        let timer_input = self.timer_input();
 
-    //Returns true if an interrupt occured
-    pub fn update_timers(&mut self, cycles_elasped: u8) -> bool {
-        self.update_source = self.update_source.wrapping_add(cycles_elasped);
-        let mut interrupt_request = false;
-
-        //source timer runs at 1/4 the speed of the clock
-        if self.update_source >= 4 {
-            self.source_timer = self.source_timer.wrapping_add(1);
-            self.update_source -= 4;
-
-            //The divider timer runs at 1/16 the speed of the source timer
-            self.div_prescaler = self.div_prescaler.wrapping_add(1);
-            if self.div_prescaler == 16 {
-                self.memory_registers[DIV] = self.memory_registers[DIV].wrapping_add(1);
-                self.div_prescaler = 0;
+        match index - TIMER_ADDR_START {
+            DIV => self.system_counter = 0,
+            TIMA => match self.tima_reload {
+                TimaReload::Reloading => {}
+                _ => {
+                    self.memory_registers[TIMA] = value;
+                    self.tima_reload = TimaReload::Idle;
+                }
+            },
+            TMA => {
+                self.memory_registers[TMA] = value;
+                if self.tima_reload == TimaReload::Reloading {
+                    self.memory_registers[TIMA] = value;
+                }
             }
+            TAC => self.memory_registers[TAC] = value & !TAC_UNUSED_BITS,
+            _ => {}
+        }
 
-            //The tima timer's frequence is adjustable by the TAC register
-            if self.memory_registers[TAC] & 0x4 > 0 {
-                let counter_threshold = match self.memory_registers[TAC] & 0x3 {
-                    0 => 64_u8,
-                    1 => 1_u8,
-                    2 => 4_u8,
-                    3 => 16_u8,
-                    _ => 0_u8,
-                };
-
-                if self.source_timer >= counter_threshold {
-                    self.source_timer = 0;
-                    if self.memory_registers[TIMA] == 255 {
-                        //on overflow the counter gets reloaded with the value from the TMA register
-                        self.memory_registers[TIMA] = self.memory_registers[TMA];
-                        interrupt_request = true;
-                    } else {
-                        self.memory_registers[TIMA] = self.memory_registers[TIMA].wrapping_add(1);
-                    }
+        if timer_input && !self.timer_input() {
+            self.increment_tima();
+        }
+    }
+
+    //Advances the timers by a number of T-cycles.  Returns true if an interrupt occured.
+    pub fn update_timers(&mut self, cycles_elasped: u32) -> bool {
+        for _ in 0..cycles_elasped {
+            //The reload moves on one M-cycle after the overflow and again one M-cycle later
+            if self.tima_reload != TimaReload::Idle {
+                self.reload_cycles -= 1;
+                if self.reload_cycles == 0 {
+                    self.tima_reload = match self.tima_reload {
+                        TimaReload::Overflowed => {
+                            //on overflow the counter gets reloaded with the value from the TMA register
+                            self.memory_registers[TIMA] = self.memory_registers[TMA];
+                            self.interrupt_request = true;
+                            self.reload_cycles = RELOAD_STEP_CYCLES;
+                            TimaReload::Reloading
+                        }
+                        _ => TimaReload::Idle,
+                    };
                 }
             }
+
+            let timer_input = self.timer_input();
+            self.system_counter = self.system_counter.wrapping_add(1);
+            if timer_input && !self.timer_input() {
+                self.increment_tima();
+            }
         }
+
         //return
-        interrupt_request
+        std::mem::take(&mut self.interrupt_request)
+    }
+}
+
+//Private methods
+impl Timer {
+    //The signal whose falling edges increment TIMA
+    fn timer_input(&self) -> bool {
+        let tac = self.memory_registers[TAC];
+        let bit = TAC_COUNTER_BITS[(tac & TAC_CLOCK_SELECT) as usize];
+
+        tac & TAC_ENABLE > 0 && self.system_counter & (1 << bit) > 0
+    }
+
+    fn increment_tima(&mut self) {
+        let (tima, overflow) = self.memory_registers[TIMA].overflowing_add(1);
+        self.memory_registers[TIMA] = tima;
+        if overflow {
+            self.tima_reload = TimaReload::Overflowed;
+            self.reload_cycles = RELOAD_STEP_CYCLES;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMA_REG: usize = 0xff05;
+    const TAC_262144_HZ: u8 = TAC_ENABLE | 0x01; //Counter bit 3
+    const TAC_65536_HZ: u8 = TAC_ENABLE | 0x02; //Counter bit 5
+
+    #[test]
+    fn tima_counts_falling_edges_of_the_selected_bit() {
+        let mut timer = Timer::new();
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+
+        timer.update_timers(15);
+        assert_eq!(timer.read_memory(TIMA_REG), 0);
+        timer.update_timers(1);
+        assert_eq!(timer.read_memory(TIMA_REG), 1);
+        timer.update_timers(16 * 10);
+        assert_eq!(timer.read_memory(TIMA_REG), 11);
+        assert_eq!(timer.read_memory(DIV_REG), 0);
+        timer.update_timers(256 - 176);
+        assert_eq!(timer.read_memory(DIV_REG), 1);
+    }
+
+    #[test]
+    fn writing_div_ticks_tima_when_the_selected_bit_is_high() {
+        let mut timer = Timer::new();
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+
+        //Bit 3 is high
+        timer.update_timers(8);
+        timer.write_memory(DIV_REG, 0x12);
+        assert_eq!(timer.read_memory(TIMA_REG), 1);
+        assert_eq!(timer.read_memory(DIV_REG), 0);
+
+        //Bit 3 is low
+        timer.update_timers(4);
+        timer.write_memory(DIV_REG, 0);
+        assert_eq!(timer.read_memory(TIMA_REG), 1);
+
+        //The reset also restarts the count, the next tick comes 16 T-cycles later
+        timer.update_timers(15);
+        assert_eq!(timer.read_memory(TIMA_REG), 1);
+        timer.update_timers(1);
+        assert_eq!(timer.read_memory(TIMA_REG), 2);
+    }
+
+    #[test]
+    fn changing_tac_ticks_tima_when_the_timer_input_falls() {
+        let mut timer = Timer::new();
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+
+        //Bit 3 is high and bit 5 is low, switching to bit 5 is a falling edge
+        timer.update_timers(8);
+        timer.write_memory(TAC_REG, TAC_65536_HZ);
+        assert_eq!(timer.read_memory(TIMA_REG), 1);
+
+        //Switching back to the high bit is a rising edge
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+        assert_eq!(timer.read_memory(TIMA_REG), 1);
+
+        //Disabling the timer while the bit is high is a falling edge as well
+        timer.write_memory(TAC_REG, TAC_262144_HZ & !TAC_ENABLE);
+        assert_eq!(timer.read_memory(TIMA_REG), 2);
+
+        //Nothing counts while disabled
+        timer.update_timers(64);
+        assert_eq!(timer.read_memory(TIMA_REG), 2);
+        assert_eq!(timer.read_memory(TAC_REG), 0xf9);
+    }
+
+    //Returns a timer whose TIMA has just overflowed, with 0x40 in TMA
+    fn overflowed_timer() -> Timer {
+        let mut timer = Timer::new();
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+        timer.write_memory(TMA_REG, 0x40);
+        timer.write_memory(TIMA_REG, 0xff);
+
+        assert!(!timer.update_timers(16));
+        timer
+    }
+
+    #[test]
+    fn tima_is_reloaded_one_m_cycle_after_overflowing() {
+        let mut timer = overflowed_timer();
+        assert_eq!(timer.read_memory(TIMA_REG), 0);
+
+        assert!(timer.update_timers(4));
+        assert_eq!(timer.read_memory(TIMA_REG), 0x40);
+    }
+
+    #[test]
+    fn tima_reads_0_for_a_whole_m_cycle_within_one_update() {
+        let mut timer = Timer::new();
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+        timer.write_memory(TMA_REG, 0x40);
+        timer.write_memory(TIMA_REG, 0xff);
+
+        //Overflows after 16 T-cycles
+        assert!(!timer.update_timers(19));
+        assert_eq!(timer.read_memory(TIMA_REG), 0);
+
+        let mut timer = Timer::new();
+        timer.write_memory(TAC_REG, TAC_262144_HZ);
+        timer.write_memory(TMA_REG, 0x40);
+        timer.write_memory(TIMA_REG, 0xff);
+
+        assert!(timer.update_timers(20));
+        assert_eq!(timer.read_memory(TIMA_REG), 0x40);
+    }
+
+    #[test]
+    fn writing_tima_before_the_reload_cancels_it() {
+        let mut timer = overflowed_timer();
+        timer.write_memory(TIMA_REG, 0x80);
+
+        assert!(!timer.update_timers(4));
+        assert_eq!(timer.read_memory(TIMA_REG), 0x80);
+    }
+
+    #[test]
+    fn tima_writes_are_ignored_and_tma_writes_go_through_during_the_reload() {
+        let mut timer = overflowed_timer();
+        assert!(timer.update_timers(4));
+
+        timer.write_memory(TIMA_REG, 0x99);
+        assert_eq!(timer.read_memory(TIMA_REG), 0x40);
+        timer.write_memory(TMA_REG, 0x55);
+        assert_eq!(timer.read_memory(TIMA_REG), 0x55);
+
+        //The reload is over one M-cycle later
+        timer.update_timers(3);
+        timer.write_memory(TIMA_REG, 0x99);
+        assert_eq!(timer.read_memory(TIMA_REG), 0x55);
+        timer.update_timers(1);
+        timer.write_memory(TMA_REG, 0x66);
+        assert_eq!(timer.read_memory(TIMA_REG), 0x55);
+        timer.write_memory(TIMA_REG, 0x99);
+        assert_eq!(timer.read_memory(TIMA_REG), 0x99);
     }
 }
@@ -0,0 +1,121 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+use crate::apu::APU_ADDR_START;
+use crate::cpu::CPU_CLOCK_HZ;
+
+//Logs the writes to the sound registers as a VGM file: https://vgmrips.net/wiki/VGM_Specification
+//Timestamps come from the cpu's cycle count and are stored as waits in 44100 Hz samples, the rate every VGM uses.
+
+const VERSION: u32 = 0x161; //First version with the Game Boy DMG
+const HEADER_SIZE: u32 = 0x100;
+const VGM_SAMPLE_RATE: u64 = 44100;
+
+//Header offsets
+const EOF_OFFSET: u64 = 0x04;
+const VERSION_OFFSET: u64 = 0x08;
+const TOTAL_SAMPLES_OFFSET: u64 = 0x18;
+const DATA_OFFSET: u64 = 0x34; //Stored relative to itself
+const DMG_CLOCK_OFFSET: u64 = 0x80;
+
+//Commands
+const DMG_WRITE: u8 = 0xb3; //Register (from 0xff10), value
+const WAIT: u8 = 0x61; //16-bit number of samples
+const WAIT_NTSC_FRAME: u8 = 0x62;
+const WAIT_PAL_FRAME: u8 = 0x63;
+const WAIT_SHORT: u8 = 0x70; //0x70-0x7f wait 1 to 16 samples
+const END_OF_DATA: u8 = 0x66;
+
+const NTSC_FRAME_SAMPLES: u64 = 735;
+const PAL_FRAME_SAMPLES: u64 = 882;
+const MAX_SHORT_WAIT: u64 = 16;
+
+pub struct VgmWriter {
+    file: BufWriter<File>,
+    start_cycle: u64,
+    samples_written: u64, //Samples waited so far
+    size: u32,            //Bytes in the file
+}
+
+impl VgmWriter {
+    //`cycle` is the cpu's cycle count when logging starts
+    pub fn create(path: &str, cycle: u64) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+
+        let mut header = [0u8; HEADER_SIZE as usize];
+        header[..4].copy_from_slice(b"Vgm ");
+        let mut field = |offset: u64, value: u32| {
+            header[offset as usize..offset as usize + 4].copy_from_slice(&value.to_le_bytes());
+        };
+        field(VERSION_OFFSET, VERSION);
+        field(DATA_OFFSET, HEADER_SIZE - DATA_OFFSET as u32);
+        field(DMG_CLOCK_OFFSET, CPU_CLOCK_HZ as u32);
+        file.write_all(&header)?;
+
+        Ok(Self {
+            file,
+            start_cycle: cycle,
+            samples_written: 0,
+            size: HEADER_SIZE,
+        })
+    }
+
+    //Logs a write to 0xff10-0xff3f
+    pub fn write_register(&mut self, address: usize, value: u8, cycle: u64) -> io::Result<()> {
+        self.wait_until(cycle)?;
+        self.write(&[DMG_WRITE, (address - APU_ADDR_START) as u8, value])
+    }
+
+    //Ends the log at `cycle` and fills in the header
+    pub fn finish(mut self, cycle: u64) -> io::Result<()> {
+        self.wait_until(cycle)?;
+        self.write(&[END_OF_DATA])?;
+
+        self.file.seek(SeekFrom::Start(EOF_OFFSET))?;
+        self.file.write_all(&(self.size - EOF_OFFSET as u32).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(TOTAL_SAMPLES_OFFSET))?;
+        self.file.write_all(&(self.samples_written as u32).to_le_bytes())?;
+
+        self.file.flush()
+    }
+}
+
+//Private methods
+impl VgmWriter {
+    fn wait_until(&mut self, cycle: u64) -> io::Result<()> {
+        let sample = (cycle - self.start_cycle) * VGM_SAMPLE_RATE / CPU_CLOCK_HZ;
+        let mut wait = sample.saturating_sub(self.samples_written);
+        self.samples_written += wait;
+
+        while wait > 0 {
+            let samples = match wait {
+                NTSC_FRAME_SAMPLES => {
+                    self.write(&[WAIT_NTSC_FRAME])?;
+                    NTSC_FRAME_SAMPLES
+                }
+                PAL_FRAME_SAMPLES => {
+                    self.write(&[WAIT_PAL_FRAME])?;
+                    PAL_FRAME_SAMPLES
+                }
+                1..=MAX_SHORT_WAIT => {
+                    self.write(&[WAIT_SHORT + (wait - 1) as u8])?;
+                    wait
+                }
+                _ => {
+                    let samples = wait.min(u16::MAX as u64);
+                    let [low, high] = (samples as u16).to_le_bytes();
+                    self.write(&[WAIT, low, high])?;
+                    samples
+                }
+            };
+            wait -= samples;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.size += bytes.len() as u32;
+        self.file.write_all(bytes)
+    }
+}
@@ -8,21 +8,26 @@ const BLOCK_0_END: usize = 0x87ff;
 const BLOCK_1_START: usize = 0x8800;
 const BLOCK_1_END: usize = 0x8fff;
 
-const BLOCK_2_START: usize = 0x9000;
+pub const BLOCK_2_START: usize = 0x9000;
 pub const BLOCK_2_END: usize = 0x97ff;
 
 //Tile maps using bytes as indexes for the tiles in blocks
-const TILE_MAP_1_START: usize = 0x9800;
+pub const TILE_MAP_1_START: usize = 0x9800;
 const TILE_MAP_1_END: usize = 0x9BFF;
 
-const TILE_MAP_2_START: usize = 0x9C00;
+pub const TILE_MAP_2_START: usize = 0x9C00;
 const TILE_MAP2_END: usize = 0x9FFF;
 
 const TILE_RAM_SIZE: usize = TILE_MAP2_END - TILE_MAP_1_START + 1;
 
-const BLOCKS_PER_VRAM: usize = 3;
-const TILES_PER_BANK: usize = (BLOCK_0_END - BLOCK_0_START + 1) / BYTES_PER_TILE;
-const BYTES_PER_TILE: usize = 16;
+pub const BLOCKS_PER_VRAM: usize = 3;
+pub const TILES_PER_BANK: usize = (BLOCK_0_END - BLOCK_0_START + 1) / BYTES_PER_TILE;
+pub const BYTES_PER_TILE: usize = 16;
+pub const LINES_PER_TILE: usize = 8;
+pub const BYTES_PER_TILE_LINE: usize = 2; //The low bitplane followed by the high bitplane
+
+//Color indexes (0-3) of the 8 pixels of a tile line, left to right
+pub type TilePixelLine = [u8; 8];
 
 pub struct Vram {
     //There are 3 different memory block: https://gbdev.io/pandocs/Tile_Data.html
@@ -38,69 +43,46 @@ impl Vram {
         }
     }
 
-    pub fn cache_tile_line(&self, block_index: usize, title_index: usize, line_index: usize) -> TilePixelLine {
-        let mut pixel_data = TilePixelLine::new();
-
-        if block_index >= BLOCKS_PER_VRAM || title_index >= TILES_PER_BANK || line_index >= 8 {
-            //TODO:log an error here
-        } else {
-            pixel_data.low_byte = self.tile_ram[block_index].block[title_index].tile[line_index];
-            pixel_data.hight_byte = self.tile_ram[block_index].block[title_index].tile[line_index + 1];
+    //Returns the decoded pixels of a tile line.  None if any of the indexes is out of range.
+    pub fn cache_tile_line(
+        &self,
+        block_index: usize,
+        tile_index: usize,
+        line_index: usize,
+    ) -> Option<&TilePixelLine> {
+        if block_index >= BLOCKS_PER_VRAM || tile_index >= TILES_PER_BANK || line_index >= LINES_PER_TILE {
+            return None;
         }
 
-        //return
-        pixel_data
+        Some(&self.tile_ram[block_index].block[tile_index].pixels[line_index])
     }
 
-    //Reads a specified byte from vram.  Used by the cpu.
-    pub fn read_vram_tile(&self, mut address: usize) -> u8 {
-        let block_index: usize;
-        match address {
-            BLOCK_0_START..=BLOCK_0_END => {
-                address -= BLOCK_0_START;
-                block_index = 0;
-            }
-            BLOCK_1_START..=BLOCK_1_END => {
-                address -= BLOCK_1_START;
-                block_index = 1;
-            }
-            BLOCK_2_START..=BLOCK_2_END => {
-                address -= BLOCK_2_START;
-                block_index = 2;
-            }
-            _ => block_index = 0, //TODO: add some kind of error logging here
-        }
+    //Returns the decoded pixels of a line of the tile starting at tile_address.  Used by the renderers.
+    pub fn tile_line_at(&self, tile_address: usize, line_index: u8) -> TilePixelLine {
+        let (block_index, offset) = Self::block_offset(tile_address);
+        self.tile_ram[block_index].block[offset / BYTES_PER_TILE].pixels[line_index as usize % LINES_PER_TILE]
+    }
 
-        let tile_index = address / BYTES_PER_TILE;
-        let byte_index = address % BYTES_PER_TILE;
+    //Reads a specified byte from vram.  Used by the cpu.
+    pub fn read_vram_tile(&self, address: usize) -> u8 {
+        let (block_index, offset) = Self::block_offset(address);
+        let tile_index = offset / BYTES_PER_TILE;
+        let byte_index = offset % BYTES_PER_TILE;
 
         //return
         self.tile_ram[block_index].block[tile_index].tile[byte_index]
     }
 
     //Writes a specific byte of a tile to vram.  Used by the cpu.
-    pub fn write_vram_tile(&mut self, mut address: usize, data: u8) {
-        let block_index: usize;
-        match address {
-            BLOCK_0_START..=BLOCK_0_END => {
-                address -= BLOCK_0_START;
-                block_index = 0;
-            }
-            BLOCK_1_START..=BLOCK_1_END => {
-                address -= BLOCK_1_START;
-                block_index = 1;
-            }
-            BLOCK_2_START..=BLOCK_2_END => {
-                address -= BLOCK_2_START;
-                block_index = 2;
-            }
-            _ => block_index = 0, //TODO: add some kind of error logging here
-        }
-
-        let tile_index = address / BYTES_PER_TILE;
-        let byte_index = address % BYTES_PER_TILE;
-
-        self.tile_ram[block_index].block[tile_index].tile[byte_index] = data;
+    //The line the byte belongs to is decoded again so the cache never holds stale pixels.
+    pub fn write_vram_tile(&mut self, address: usize, data: u8) {
+        let (block_index, offset) = Self::block_offset(address);
+        let tile_index = offset / BYTES_PER_TILE;
+        let byte_index = offset % BYTES_PER_TILE;
+
+        let tile = &mut self.tile_ram[block_index].block[tile_index];
+        tile.tile[byte_index] = data;
+        tile.decode_line(byte_index / 2);
     }
 
     pub fn read_vram_map(&self, mut address: usize) -> u8 {
@@ -109,6 +91,16 @@ impl Vram {
     pub fn write_vram_map(&mut self, mut address: usize, data: u8) {
         self.map_ram[address - TILE_MAP_1_START] = data;
     }
+
+    //Splits a tile data address into the block index and the offset in that block
+    fn block_offset(address: usize) -> (usize, usize) {
+        match address {
+            BLOCK_0_START..=BLOCK_0_END => (0, address - BLOCK_0_START),
+            BLOCK_1_START..=BLOCK_1_END => (1, address - BLOCK_1_START),
+            BLOCK_2_START..=BLOCK_2_END => (2, address - BLOCK_2_START),
+            _ => (0, 0), //TODO: add some kind of error logging here
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -128,27 +120,34 @@ impl Block {
 
 #[derive(Copy, Clone)]
 struct Tile {
+    //Each line is 2 bytes: the low bitplane followed by the high bitplane
     tile: [u8; BYTES_PER_TILE],
+    pixels: [TilePixelLine; LINES_PER_TILE], //Decoded copy of the bitplanes
 }
 
 impl Tile {
     pub fn new() -> Self {
         Self {
             tile: [0; BYTES_PER_TILE],
+            pixels: [[0; 8]; LINES_PER_TILE],
         }
     }
-}
 
-pub struct TilePixelLine {
-    hight_byte: u8,
-    low_byte: u8,
+    fn decode_line(&mut self, line_index: usize) {
+        self.pixels[line_index] = decode_tile_line(
+            self.tile[line_index * BYTES_PER_TILE_LINE],
+            self.tile[line_index * BYTES_PER_TILE_LINE + 1],
+        );
+    }
 }
 
-impl TilePixelLine {
-    pub fn new() -> Self {
-        Self {
-            hight_byte: 0,
-            low_byte: 0,
-        }
+//Combines the two bitplanes of a tile line into its 2-bit color indexes, leftmost pixel first
+pub fn decode_tile_line(low_byte: u8, high_byte: u8) -> TilePixelLine {
+    let mut pixels = [0; 8];
+    for (column, pixel) in pixels.iter_mut().enumerate() {
+        let bit = 7 - column;
+        *pixel = (((high_byte >> bit) & 1) << 1) | ((low_byte >> bit) & 1);
     }
+
+    pixels
 }
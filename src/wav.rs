@@ -0,0 +1,67 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+//16-bit PCM WAV writer: http://soundfile.sapp.org/doc/WaveFormat/
+//The sizes in the header are rewritten on every flush so the file stays playable if the emulator is closed
+//without finishing the recording.
+
+const HEADER_SIZE: u32 = 44;
+const RIFF_SIZE_OFFSET: u64 = 4;
+const DATA_SIZE_OFFSET: u64 = 40;
+const BITS_PER_SAMPLE: u16 = 16;
+const FORMAT_PCM: u16 = 1;
+
+pub struct WavWriter {
+    file: BufWriter<File>,
+    data_size: u32, //Bytes of samples written
+}
+
+impl WavWriter {
+    pub fn create(path: &str, sample_rate: u32, channels: u16) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let block_align = channels * BITS_PER_SAMPLE / 8;
+
+        file.write_all(b"RIFF")?;
+        file.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        file.write_all(b"WAVE")?;
+        file.write_all(b"fmt ")?;
+        file.write_all(&16u32.to_le_bytes())?;
+        file.write_all(&FORMAT_PCM.to_le_bytes())?;
+        file.write_all(&channels.to_le_bytes())?;
+        file.write_all(&sample_rate.to_le_bytes())?;
+        file.write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        file.write_all(&block_align.to_le_bytes())?;
+        file.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+        file.write_all(b"data")?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        Ok(Self { file, data_size: 0 })
+    }
+
+    //Samples of all channels interleaved, e.g. left, right, left, right
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_size += (samples.len() * 2) as u32;
+
+        Ok(())
+    }
+
+    //Updates the header with the number of samples written so far
+    pub fn flush(&mut self) -> io::Result<()> {
+        let end = self.file.stream_position()?;
+
+        self.file.seek(SeekFrom::Start(RIFF_SIZE_OFFSET))?;
+        self.file.write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(DATA_SIZE_OFFSET))?;
+        self.file.write_all(&self.data_size.to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(end))?;
+
+        self.file.flush()
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.flush()
+    }
+}